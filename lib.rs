@@ -1,35 +1,70 @@
-#![cfg_attr(not(feature = "std"), no_std)]
-
-use ink_lang as ink;
+#![cfg_attr(not(feature = "std"), no_std, no_main)]
 
 #[ink::contract]
 mod cryptopunks {
-    use ink_storage::lazy::Mapping;
+    use ink::prelude::vec::Vec;
+    use ink::storage::Mapping;
 
     #[ink(storage)]
-    #[derive(Default, ink_storage::traits::SpreadAllocate)]
     pub struct Cryptopunks {
         owner: AccountId,
+        /// Account proposed as the next owner, which has yet to accept.
+        pending_owner: Option<AccountId>,
         total_supply: u32,
         punks_remaining_to_assign: u32,
         number_of_punks_to_reserve: u32,
         number_of_punks_reserved: u32,
         next_punk_index_to_assign: u32,
         punk_index_to_address: Mapping<u32, AccountId>,
-        punks_offered_for_sale: Mapping<u32, Offer>,
+        punks_offered_for_sale: Mapping<u32, StoredOffer>,
+        /// Indices of all punks currently offered for sale by their position, as
+        /// `Mapping` can't be iterated. Kept out of the contract root, whose
+        /// encoding has to fit a fixed buffer however many punks are listed.
+        for_sale_indices: Mapping<u32, u32>,
+        /// Position of each listed punk in `for_sale_indices`.
+        for_sale_position: Mapping<u32, u32>,
+        /// Number of punks currently offered for sale.
+        for_sale_count: u32,
+        /// Punks their holder deliberately keeps off the market.
+        held_off_market: Mapping<u32, ()>,
+        active_offers_of: Mapping<AccountId, u32>,
+        reserved_by: Mapping<AccountId, u32>,
+        /// Ring buffer of the most recently assigned punk indices.
+        recent_assignments: [u32; RECENT_ASSIGNMENTS],
+        /// Number of assignments ever recorded in `recent_assignments`.
+        assignments_recorded: u32,
+        /// Claimable earnings, e.g. sale proceeds and fees.
         pending_withdrawals: Mapping<AccountId, u128>,
+        /// Claimable refunds of overpayments, kept apart from the earnings.
+        pending_refunds: Mapping<AccountId, Balance>,
+        /// Sum of all pending withdrawals and refunds.
+        total_pending: Balance,
+        /// Recent credits of each account's pending balance, oldest first, each
+        /// with the block from which it can be withdrawn. At most
+        /// `MAX_LOCKED_CREDITS` are kept per account.
+        locked_withdrawals: Mapping<AccountId, Vec<LockedCredit>>,
+        /// Number of blocks a new credit is locked before it can be withdrawn.
+        withdrawal_delay_blocks: BlockNumber,
         balance_of: Mapping<AccountId, u32>,
+        /// Number of accounts holding at least one punk.
+        unique_holders: u32,
+        total_spent: Mapping<AccountId, Balance>,
+        total_earned: Mapping<AccountId, Balance>,
+        claimed_at: Mapping<u32, BlockNumber>,
+        /// First owner of each assigned punk, unchanged by later transfers.
+        original_minter: Mapping<u32, AccountId>,
+        last_transfer_block: Mapping<u32, BlockNumber>,
+        min_hold_blocks: BlockNumber,
+        buying_paused: bool,
+        transfer_fee: Balance,
+        decimals: u8,
+        /// Sequence number of the last emitted event.
+        event_seq: u64,
     }
 
-    #[derive(
-        Default,
-        scale::Encode,
-        scale::Decode,
-        ink_storage::traits::PackedLayout,
-        ink_storage::traits::SpreadLayout,
-    )]
+    #[derive(Debug, PartialEq, Eq, scale::Encode, scale::Decode)]
     #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
-    struct Offer {
+    pub struct Offer {
         is_for_sale: bool,
         punk_index: u32,
         seller: AccountId,
@@ -37,10 +72,112 @@ mod cryptopunks {
         only_sell_to: Option<AccountId>,
     }
 
+    /// An `Offer` as stored in `punks_offered_for_sale`, without the punk index
+    /// which is already the key.
+    #[derive(Debug, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    struct StoredOffer {
+        is_for_sale: bool,
+        seller: AccountId,
+        min_value: Balance,
+        only_sell_to: Option<AccountId>,
+    }
+
+    impl StoredOffer {
+        fn with_index(self, punk_index: u32) -> Offer {
+            Offer {
+                is_for_sale: self.is_for_sale,
+                punk_index,
+                seller: self.seller,
+                min_value: self.min_value,
+                only_sell_to: self.only_sell_to,
+            }
+        }
+    }
+
+    /// Errors that can occur upon calling this contract.
+    #[derive(Debug, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub enum Error {
+        /// The caller is not the owner of the contract.
+        NotOwner,
+        /// The caller is not the proposed owner of the contract.
+        NotPendingOwner,
+        /// The punk has not been assigned to anyone yet.
+        PunkNotAssigned,
+        /// All punks have been assigned.
+        SoldOut,
+        /// The punk index is out of the valid range.
+        PunkIndexOutOfRange,
+        /// The punk has already been assigned.
+        PunkAlreadyAssigned,
+        /// The same punk index is given more than once.
+        DuplicateIndex,
+        /// The punk was assigned in a prior deployment and awaits `seed_owner`.
+        PunkNotSeeded,
+        /// Reserving would exceed the number of punks to reserve for the owner.
+        ReservationQuotaExceeded,
+        /// The caller does not own the punk.
+        NotPunkOwner,
+        /// The punk would be transferred to its current owner.
+        SelfTransfer,
+        /// The punk would be transferred to the zero account, losing it.
+        ZeroAddress,
+        /// The punk was claimed too recently to be transferred or offered.
+        HoldPeriodActive,
+        /// Buying punks is paused by the owner.
+        BuyingPaused,
+        /// The punk is not offered for sale.
+        PunkNotForSale,
+        /// The punk is already offered for sale; use `relist` to change the offer.
+        PunkAlreadyListed,
+        /// The offer is reserved for a different buyer.
+        PunkReservedForOtherBuyer,
+        /// The transferred value is below the offer's minimum price or the
+        /// transfer fee.
+        InsufficientPayment { expected: Balance, actual: Balance },
+        /// The summed prices of the offers overflow the balance type.
+        PriceOverflow,
+        /// The seller of the offer no longer owns the punk.
+        SellerNotOwner,
+        /// The price of the offer differs from the price the buyer expected.
+        PriceChanged,
+        /// The requested amount is zero.
+        ZeroAmount,
+        /// The requested amount exceeds the caller's pending withdrawals.
+        AmountExceedsPending,
+        /// The caller has no pending withdrawals.
+        NoPendingWithdrawals,
+        /// Transferring the withdrawn funds failed; they remain claimable.
+        TransferFailed,
+        /// The shares don't sum up to 10000 basis points.
+        InvalidShares,
+        /// The contract balance doesn't cover the pending withdrawals.
+        Insolvent,
+        /// The contract can't be terminated while withdrawals are pending.
+        PendingWithdrawalsExist,
+        /// The transfer memo is longer than `MAX_MEMO_LEN` bytes.
+        MemoTooLong,
+        /// The pending balance was credited too recently to be withdrawn.
+        WithdrawalLocked,
+        /// The withdrawal delay exceeds `MAX_WITHDRAWAL_DELAY_BLOCKS`.
+        WithdrawalDelayTooLong,
+    }
+
+    /// Type alias for the contract's result type.
+    pub type Result<T> = core::result::Result<T, Error>;
+
+    /// A credited amount and the block from which it can be withdrawn.
+    type LockedCredit = (Balance, BlockNumber);
+
     #[ink(event)]
     pub struct PunkNoLongerForSale {
         #[ink(topic)]
         punk_index: u32,
+        seq: u64,
     }
 
     #[ink(event)]
@@ -50,6 +187,8 @@ mod cryptopunks {
         min_sale_price: Balance,
         #[ink(topic)]
         address: Option<AccountId>,
+        created_at: Timestamp,
+        seq: u64,
     }
 
     #[ink(event)]
@@ -59,6 +198,7 @@ mod cryptopunks {
         #[ink(topic)]
         to: AccountId,
         value: u128,
+        seq: u64,
     }
 
     #[ink(event)]
@@ -68,6 +208,54 @@ mod cryptopunks {
         #[ink(topic)]
         to: AccountId,
         punk_index: u32,
+        memo: Vec<u8>,
+        seq: u64,
+    }
+
+    #[ink(event)]
+    pub struct BatchAssigned {
+        #[ink(topic)]
+        to: AccountId,
+        count: u32,
+        seq: u64,
+    }
+
+    #[ink(event)]
+    pub struct ReservationComplete {
+        total_reserved: u32,
+        seq: u64,
+    }
+
+    #[ink(event)]
+    pub struct Paused {
+        #[ink(topic)]
+        by: AccountId,
+        seq: u64,
+    }
+
+    #[ink(event)]
+    pub struct Unpaused {
+        #[ink(topic)]
+        by: AccountId,
+        seq: u64,
+    }
+
+    #[ink(event)]
+    pub struct OwnershipProposed {
+        #[ink(topic)]
+        from: AccountId,
+        #[ink(topic)]
+        to: AccountId,
+        seq: u64,
+    }
+
+    #[ink(event)]
+    pub struct OwnershipTransferred {
+        #[ink(topic)]
+        from: AccountId,
+        #[ink(topic)]
+        to: AccountId,
+        seq: u64,
     }
 
     #[ink(event)]
@@ -75,200 +263,1450 @@ mod cryptopunks {
         #[ink(topic)]
         to: AccountId,
         punk_index: u32,
+        seq: u64,
     }
 
+    /// Upper bound for the total supply of any deployment, bounding storage and
+    /// enumeration costs.
+    pub const MAX_SUPPLY: u32 = 10000;
+
+    /// Number of recently assigned punks returned by `recent_punks`.
+    const RECENT_ASSIGNMENTS: usize = 16;
+
+    /// Token decimals of a default Substrate chain.
+    const DEFAULT_DECIMALS: u8 = 12;
+
+    /// Upper bound for the withdrawal delay, about a week of 6 second blocks.
+    pub const MAX_WITHDRAWAL_DELAY_BLOCKS: BlockNumber = 100_800;
+
+    /// Number of separately locked credits kept per account.
+    const MAX_LOCKED_CREDITS: usize = 2;
+
+    /// Maximum length in bytes of a memo attached to a punk transfer.
+    const MAX_MEMO_LEN: usize = 64;
+
     impl Cryptopunks {
         #[ink(constructor)]
         pub fn new() -> Self {
-            ink_lang::codegen::initialize_contract(|contract: &mut Self| {
-                contract.owner = Self::env().caller();
-                contract.total_supply = 1000;
-                contract.punks_remaining_to_assign = 1000;
-                contract.number_of_punks_to_reserve = 1000;
-                contract.number_of_punks_reserved = 0;
-                contract.next_punk_index_to_assign = 0;
-            })
+            Self::new_with_decimals(DEFAULT_DECIMALS)
         }
 
-        #[ink(message)]
-        pub fn reserve_punks_for_owner(&mut self, max_for_this_run: u32) {
-            assert_eq!(self.env().caller(), self.owner, "Caller is not owner!");
+        /// Creates the contract for a chain whose native token uses `decimals` decimals.
+        #[ink(constructor)]
+        pub fn new_with_decimals(decimals: u8) -> Self {
+            Self {
+                owner: Self::env().caller(),
+                pending_owner: None,
+                total_supply: 1000,
+                punks_remaining_to_assign: 1000,
+                number_of_punks_to_reserve: 1000,
+                number_of_punks_reserved: 0,
+                next_punk_index_to_assign: 0,
+                punk_index_to_address: Mapping::default(),
+                punks_offered_for_sale: Mapping::default(),
+                for_sale_indices: Mapping::default(),
+                for_sale_position: Mapping::default(),
+                for_sale_count: 0,
+                held_off_market: Mapping::default(),
+                active_offers_of: Mapping::default(),
+                reserved_by: Mapping::default(),
+                recent_assignments: [0; RECENT_ASSIGNMENTS],
+                assignments_recorded: 0,
+                pending_withdrawals: Mapping::default(),
+                pending_refunds: Mapping::default(),
+                total_pending: 0,
+                locked_withdrawals: Mapping::default(),
+                withdrawal_delay_blocks: 0,
+                balance_of: Mapping::default(),
+                unique_holders: 0,
+                total_spent: Mapping::default(),
+                total_earned: Mapping::default(),
+                claimed_at: Mapping::default(),
+                original_minter: Mapping::default(),
+                last_transfer_block: Mapping::default(),
+                min_hold_blocks: 0,
+                buying_paused: false,
+                transfer_fee: 0,
+                decimals,
+                event_seq: 0,
+            }
+        }
+
+        /// Restores the core counters of a prior deployment, assuming the punks
+        /// `0..next_index` were already assigned. Their owners, and those of punks
+        /// claimed above `next_index`, have to be seeded with `seed_owner`
+        /// afterwards.
+        #[ink(constructor)]
+        pub fn migrate(owner: AccountId, total_supply: u32, next_index: u32) -> Self {
+            assert!(
+                total_supply <= MAX_SUPPLY,
+                "Total supply exceeds MAX_SUPPLY!"
+            );
             assert!(
-                self.number_of_punks_reserved <= self.number_of_punks_to_reserve,
-                "Already all reservable punks reserved!"
+                next_index <= total_supply,
+                "Next index exceeds total supply!"
             );
+            let mut contract = Self::new();
+            contract.owner = owner;
+            contract.total_supply = total_supply;
+            contract.punks_remaining_to_assign = total_supply - next_index;
+            contract.number_of_punks_to_reserve = total_supply;
+            contract.number_of_punks_reserved = next_index;
+            contract.next_punk_index_to_assign = next_index;
+            contract
+        }
+
+        /// Seeds the owner of a punk assigned in a prior deployment. Punks at or
+        /// above the migrated `next_index` were claimed with `get_punk` there and
+        /// no longer count as remaining to assign. Only callable by the owner.
+        #[ink(message)]
+        pub fn seed_owner(&mut self, punk_index: u32, owner: AccountId) -> Result<()> {
+            self.require_owner()?;
+            if punk_index >= self.total_supply {
+                return Err(Error::PunkIndexOutOfRange);
+            }
+            if self.punk_index_to_address.contains(punk_index) {
+                return Err(Error::PunkAlreadyAssigned);
+            }
+            self.assign_punk(owner, punk_index);
+            self.increase_balance(owner, 1);
+            if punk_index >= self.next_punk_index_to_assign {
+                self.punks_remaining_to_assign -= 1;
+            }
+            Ok(())
+        }
+
+        /// Returns the owner of the punk, if it has been assigned.
+        #[ink(message)]
+        pub fn owner_of(&self, punk_index: u32) -> Option<AccountId> {
+            self.punk_index_to_address.get(punk_index)
+        }
+
+        /// Returns the account the punk was first assigned to, if it has been
+        /// assigned.
+        #[ink(message)]
+        pub fn minter_of(&self, punk_index: u32) -> Option<AccountId> {
+            self.original_minter.get(punk_index)
+        }
+
+        /// Returns the block in which the punk last changed its owner, if it has
+        /// been assigned.
+        #[ink(message)]
+        pub fn last_transfer_block_of(&self, punk_index: u32) -> Option<BlockNumber> {
+            self.last_transfer_block.get(punk_index)
+        }
+
+        /// Returns the decimals of the chain's native token, used by UIs to format
+        /// offer prices and balances.
+        #[ink(message)]
+        pub fn decimals(&self) -> u8 {
+            self.decimals
+        }
+
+        /// Returns the account id of this contract.
+        #[ink(message)]
+        pub fn contract_account(&self) -> AccountId {
+            self.env().account_id()
+        }
+
+        /// Reserves up to `max_for_this_run` of the next unassigned punks for the
+        /// owner and returns how many were reserved. Stops early at the end of the
+        /// supply; punks claimed in between are skipped.
+        #[ink(message)]
+        pub fn reserve_punks_for_owner(&mut self, max_for_this_run: u32) -> Result<u32> {
+            self.require_owner()?;
+            let number_to_reserve = max_for_this_run
+                .min(self.owner_reservable_remaining())
+                .min(self.punks_remaining_to_assign);
             let mut number_punks_reserved_this_run: u32 = 0;
-            while number_punks_reserved_this_run < self.number_of_punks_to_reserve
-                && number_punks_reserved_this_run < max_for_this_run
+            while number_punks_reserved_this_run < number_to_reserve
+                && self.next_punk_index_to_assign < self.total_supply
             {
-                self.punk_index_to_address
-                    .insert(self.next_punk_index_to_assign, &self.env().caller());
-                self.env().emit_event(Assign {
-                    to: self.env().caller(),
-                    punk_index: self.next_punk_index_to_assign,
-                });
-                number_punks_reserved_this_run += 1;
+                if !self
+                    .punk_index_to_address
+                    .contains(self.next_punk_index_to_assign)
+                {
+                    self.assign_punk(self.env().caller(), self.next_punk_index_to_assign);
+                    number_punks_reserved_this_run += 1;
+                }
                 self.next_punk_index_to_assign += 1;
             }
             self.punks_remaining_to_assign -= number_punks_reserved_this_run;
-            self.number_of_punks_reserved += number_punks_reserved_this_run;
-            let previous_balance = self.balance_of.get(self.env().caller()).unwrap_or(0);
-            self.balance_of.insert(
-                self.env().caller(),
-                &(previous_balance + number_punks_reserved_this_run),
-            );
+            self.add_reserved(self.env().caller(), number_punks_reserved_this_run);
+            self.increase_balance(self.env().caller(), number_punks_reserved_this_run);
+            Ok(number_punks_reserved_this_run)
+        }
+
+        /// Proposes `new_owner` as the next owner. Ownership, including the right to
+        /// reserve the remaining reservable punks, only moves once they call
+        /// `accept_ownership`. Only callable by the owner.
+        #[ink(message)]
+        pub fn propose_owner(&mut self, new_owner: AccountId) -> Result<()> {
+            self.require_owner()?;
+            self.pending_owner = Some(new_owner);
+            let seq = self.next_event_seq();
+            self.env().emit_event(OwnershipProposed {
+                from: self.owner,
+                to: new_owner,
+                seq,
+            });
+            Ok(())
+        }
+
+        /// Accepts the ownership proposed by `propose_owner`. Only callable by the
+        /// proposed owner.
+        #[ink(message)]
+        pub fn accept_ownership(&mut self) -> Result<()> {
+            let caller = self.env().caller();
+            if self.pending_owner != Some(caller) {
+                return Err(Error::NotPendingOwner);
+            }
+            self.set_owner(caller);
+            Ok(())
+        }
+
+        /// Withdraws the pending ownership proposal, if any. Only callable by the
+        /// owner.
+        #[ink(message)]
+        pub fn cancel_owner_proposal(&mut self) -> Result<()> {
+            self.require_owner()?;
+            self.pending_owner = None;
+            Ok(())
+        }
+
+        /// Returns the account proposed as the next owner, if any.
+        #[ink(message)]
+        pub fn pending_owner(&self) -> Option<AccountId> {
+            self.pending_owner
+        }
+
+        /// Makes `new_owner` the owner, dropping any pending proposal.
+        fn set_owner(&mut self, new_owner: AccountId) {
+            let previous_owner = self.owner;
+            self.owner = new_owner;
+            self.pending_owner = None;
+            let seq = self.next_event_seq();
+            self.env().emit_event(OwnershipTransferred {
+                from: previous_owner,
+                to: new_owner,
+                seq,
+            });
+        }
+
+        /// Returns the owner of the contract.
+        #[ink(message)]
+        pub fn owner(&self) -> AccountId {
+            self.owner
+        }
+
+        /// Returns `NotOwner` unless the caller is the contract owner. Every
+        /// owner-gated message goes through this check.
+        fn require_owner(&self) -> Result<()> {
+            if self.env().caller() != self.owner {
+                return Err(Error::NotOwner);
+            }
+            Ok(())
+        }
+
+        /// Returns how many punks have been reserved for the owner so far.
+        #[ink(message)]
+        pub fn number_of_punks_reserved(&self) -> u32 {
+            self.number_of_punks_reserved
+        }
+
+        /// Returns how many punks were reserved for `owner`, regardless of whether
+        /// they still hold them.
+        #[ink(message)]
+        pub fn reserved_count_of(&self, owner: AccountId) -> u32 {
+            self.reserved_by.get(owner).unwrap_or(0)
+        }
+
+        /// Returns how many punks may be reserved for the owner in total.
+        #[ink(message)]
+        pub fn number_of_punks_to_reserve(&self) -> u32 {
+            self.number_of_punks_to_reserve
+        }
+
+        /// Returns how many more punks may be reserved for the owner.
+        #[ink(message)]
+        pub fn owner_reservable_remaining(&self) -> u32 {
+            self.number_of_punks_to_reserve
+                .saturating_sub(self.number_of_punks_reserved)
+        }
+
+        /// Returns `(reservable_remaining, publicly_mintable_remaining,
+        /// assigned_total)`, which add up to the total supply. The owner may
+        /// reserve the whole supply, so every unassigned punk counts as reservable
+        /// and `publicly_mintable_remaining` stays 0, although `get_punk` can
+        /// still claim any of them.
+        #[ink(message)]
+        pub fn supply_breakdown(&self) -> (u32, u32, u32) {
+            let reservable_remaining = self
+                .owner_reservable_remaining()
+                .min(self.punks_remaining_to_assign);
+            (
+                reservable_remaining,
+                self.punks_remaining_to_assign
+                    .saturating_sub(reservable_remaining),
+                self.total_supply - self.punks_remaining_to_assign,
+            )
         }
 
+        /// Returns `(total_supply, punks_remaining_to_assign, number_of_punks_to_reserve,
+        /// number_of_punks_reserved)` in one call.
         #[ink(message)]
-        pub fn get_punk(&mut self, punk_index: u32) {
-            assert!(self.punks_remaining_to_assign > 0);
-            assert_eq!(self.punk_index_to_address.get(punk_index), None);
-            self.punk_index_to_address
-                .insert(punk_index, &self.env().caller());
-            let amount = self.balance_of.get(self.env().caller()).unwrap_or(0);
-            self.balance_of.insert(self.env().caller(), &(amount + 1));
+        pub fn config(&self) -> (u32, u32, u32, u32) {
+            (
+                self.total_supply,
+                self.punks_remaining_to_assign,
+                self.number_of_punks_to_reserve,
+                self.number_of_punks_reserved,
+            )
+        }
+
+        /// Returns whether all punks have been assigned.
+        #[ink(message)]
+        pub fn is_sold_out(&self) -> bool {
+            self.punks_remaining_to_assign == 0
+        }
+
+        #[ink(message)]
+        pub fn get_punk(&mut self, punk_index: u32) -> Result<()> {
+            if punk_index >= self.total_supply {
+                return Err(Error::PunkIndexOutOfRange);
+            }
+            if self.punks_remaining_to_assign == 0 {
+                return Err(Error::SoldOut);
+            }
+            if self.punk_index_to_address.contains(punk_index) {
+                return Err(Error::PunkAlreadyAssigned);
+            }
+            if punk_index < self.next_punk_index_to_assign {
+                return Err(Error::PunkNotSeeded);
+            }
+            self.assign_punk(self.env().caller(), punk_index);
+            self.increase_balance(self.env().caller(), 1);
             self.punks_remaining_to_assign -= 1;
+            Ok(())
+        }
+
+        /// Reserves the given unassigned punks for the owner, e.g. to keep rare
+        /// ids. Only callable by the owner.
+        #[ink(message)]
+        pub fn reserve_indices(&mut self, indices: Vec<u32>) -> Result<()> {
+            self.require_owner()?;
+            let mut sorted = indices.clone();
+            sorted.sort_unstable();
+            if sorted.windows(2).any(|pair| pair[0] == pair[1]) {
+                return Err(Error::DuplicateIndex);
+            }
+            self.reserve(indices, false)
+        }
+
+        /// Reserves the punks `start..end` for the owner. All of them have to be
+        /// unassigned and below the total supply. If `silent` is set, a single
+        /// `BatchAssigned` event is emitted instead of one `Assign` per punk,
+        /// which keeps large reservations within the event buffer. Only callable
+        /// by the owner.
+        #[ink(message)]
+        pub fn reserve_range(&mut self, start: u32, end: u32, silent: bool) -> Result<()> {
+            self.require_owner()?;
+            if start > end || end > self.total_supply {
+                return Err(Error::PunkIndexOutOfRange);
+            }
+            self.reserve((start..end).collect(), silent)
+        }
+
+        /// Reserves the distinct `indices` for the caller, who has to be the owner.
+        fn reserve(&mut self, indices: Vec<u32>, silent: bool) -> Result<()> {
+            let caller = self.env().caller();
+            let count = indices.len() as u32;
+            if count > self.owner_reservable_remaining() {
+                return Err(Error::ReservationQuotaExceeded);
+            }
+            for &punk_index in &indices {
+                if punk_index >= self.total_supply {
+                    return Err(Error::PunkIndexOutOfRange);
+                }
+                if self.punk_index_to_address.contains(punk_index) {
+                    return Err(Error::PunkAlreadyAssigned);
+                }
+                if punk_index < self.next_punk_index_to_assign {
+                    return Err(Error::PunkNotSeeded);
+                }
+            }
+            for punk_index in indices {
+                if silent {
+                    self.record_assignment(caller, punk_index);
+                } else {
+                    self.assign_punk(caller, punk_index);
+                }
+            }
+            if silent {
+                let seq = self.next_event_seq();
+                self.env().emit_event(BatchAssigned {
+                    to: caller,
+                    count,
+                    seq,
+                });
+            }
+            self.punks_remaining_to_assign -= count;
+            self.add_reserved(caller, count);
+            self.increase_balance(caller, count);
+            Ok(())
+        }
+
+        /// Counts `count` punks as reserved for `owner` and signals the end of the
+        /// reservation phase once all reservable punks are reserved.
+        fn add_reserved(&mut self, owner: AccountId, count: u32) {
+            let reserved_by_owner = self.reserved_by.get(owner).unwrap_or(0);
+            self.reserved_by.insert(owner, &(reserved_by_owner + count));
+            let previously_reserved = self.number_of_punks_reserved;
+            self.number_of_punks_reserved += count;
+            if previously_reserved < self.number_of_punks_to_reserve
+                && self.number_of_punks_reserved >= self.number_of_punks_to_reserve
+            {
+                let seq = self.next_event_seq();
+                self.env().emit_event(ReservationComplete {
+                    total_reserved: self.number_of_punks_reserved,
+                    seq,
+                });
+            }
+        }
+
+        /// Asserts that the balances match the assigned punks and that the known
+        /// accounts' pending withdrawals are covered by the contract balance.
+        #[cfg(test)]
+        fn assert_invariants(&self) {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut holders: Vec<(AccountId, u32)> = Vec::new();
+            for punk_index in 0..self.total_supply {
+                if let Some(owner) = self.punk_index_to_address.get(punk_index) {
+                    match holders.iter_mut().find(|(holder, _)| *holder == owner) {
+                        Some((_, count)) => *count += 1,
+                        None => holders.push((owner, 1)),
+                    }
+                }
+            }
+            let mut total_owned = 0;
+            for &(holder, count) in &holders {
+                assert_eq!(self.balance_of.get(holder).unwrap_or(0), count);
+                total_owned += count;
+            }
+            assert_eq!(holders.len() as u32, self.unique_holders);
+            for position in 0..self.for_sale_count {
+                let punk_index = self.for_sale_indices.get(position).unwrap();
+                assert_eq!(self.for_sale_position.get(punk_index), Some(position));
+                assert!(
+                    self.punks_offered_for_sale
+                        .get(punk_index)
+                        .unwrap()
+                        .is_for_sale
+                );
+            }
+            assert_eq!(
+                total_owned,
+                self.total_supply - self.punks_remaining_to_assign
+            );
+
+            let mut known_accounts = vec![
+                accounts.alice,
+                accounts.bob,
+                accounts.charlie,
+                accounts.django,
+                accounts.eve,
+                accounts.frank,
+            ];
+            for (holder, _) in holders {
+                if !known_accounts.contains(&holder) {
+                    known_accounts.push(holder);
+                }
+            }
+            let total_pending: Balance = known_accounts
+                .into_iter()
+                .map(|account| self.pending_of(account))
+                .sum();
+            assert_eq!(total_pending, self.total_pending);
+            assert!(total_pending <= self.env().balance());
+        }
+
+        fn increase_balance(&mut self, account: AccountId, count: u32) {
+            let balance = self.balance_of.get(account).unwrap_or(0);
+            if balance == 0 && count > 0 {
+                self.unique_holders += 1;
+            }
+            self.balance_of.insert(account, &(balance + count));
+        }
+
+        fn decrease_balance(&mut self, account: AccountId, count: u32) {
+            let balance = self
+                .balance_of
+                .get(account)
+                .expect("Holder has at least 1 punk");
+            if balance == count && count > 0 {
+                self.unique_holders -= 1;
+            }
+            self.balance_of.insert(account, &(balance - count));
+        }
+
+        /// Returns the number of punks held by each of `owners`, in the same order.
+        #[ink(message)]
+        pub fn balances_of(&self, owners: Vec<AccountId>) -> Vec<u32> {
+            owners
+                .into_iter()
+                .map(|owner| self.balance_of.get(owner).unwrap_or(0))
+                .collect()
+        }
+
+        /// Returns the number of accounts holding at least one punk.
+        #[ink(message)]
+        pub fn holder_count(&self) -> u32 {
+            self.unique_holders
+        }
+
+        /// Returns the sequence number for the next emitted event, so indexers can
+        /// order the events deterministically.
+        fn next_event_seq(&mut self) -> u64 {
+            self.event_seq += 1;
+            self.event_seq
+        }
+
+        /// Records `to` as the owner of the unassigned punk and emits `Assign`.
+        /// Updating the balance and supply counters is left to the caller.
+        fn assign_punk(&mut self, to: AccountId, punk_index: u32) {
+            self.record_assignment(to, punk_index);
+            let seq = self.next_event_seq();
             self.env().emit_event(Assign {
-                to: self.env().caller(),
+                to,
                 punk_index,
+                seq,
             });
         }
 
+        /// Records `to` as the owner of the unassigned punk without emitting an
+        /// event.
+        fn record_assignment(&mut self, to: AccountId, punk_index: u32) {
+            self.punk_index_to_address.insert(punk_index, &to);
+            self.original_minter.insert(punk_index, &to);
+            self.claimed_at
+                .insert(punk_index, &self.env().block_number());
+            self.last_transfer_block
+                .insert(punk_index, &self.env().block_number());
+            let slot = self.assignments_recorded as usize % RECENT_ASSIGNMENTS;
+            self.recent_assignments[slot] = punk_index;
+            self.assignments_recorded += 1;
+        }
+
+        /// Returns the indices of the most recently assigned punks, oldest first.
+        #[ink(message)]
+        pub fn recent_punks(&self) -> Vec<u32> {
+            let recorded = self.assignments_recorded as usize;
+            let count = recorded.min(RECENT_ASSIGNMENTS);
+            (recorded - count..recorded)
+                .map(|assignment| self.recent_assignments[assignment % RECENT_ASSIGNMENTS])
+                .collect()
+        }
+
+        #[ink(message)]
+        pub fn transfer_punk(&mut self, to: AccountId, punk_index: u32) -> Result<()> {
+            self.transfer_paying_fee(to, punk_index, Vec::new(), 0)
+        }
+
+        /// Transfers the punk like `transfer_punk_with_fee`, tagging the emitted
+        /// `PunkTransfer` with `memo` of at most `MAX_MEMO_LEN` bytes.
+        #[ink(message, payable)]
+        pub fn transfer_punk_with_memo(
+            &mut self,
+            to: AccountId,
+            punk_index: u32,
+            memo: Vec<u8>,
+        ) -> Result<()> {
+            if memo.len() > MAX_MEMO_LEN {
+                return Err(Error::MemoTooLong);
+            }
+            let payment = self.env().transferred_value();
+            self.transfer_paying_fee(to, punk_index, memo, payment)
+        }
+
+        /// Transfers the punk like `transfer_punk`, paying the transfer fee with the
+        /// transferred value. The fee is credited to the contract owner, any excess
+        /// to the caller's pending withdrawals.
+        #[ink(message, payable)]
+        pub fn transfer_punk_with_fee(&mut self, to: AccountId, punk_index: u32) -> Result<()> {
+            let payment = self.env().transferred_value();
+            self.transfer_paying_fee(to, punk_index, Vec::new(), payment)
+        }
+
+        fn transfer_paying_fee(
+            &mut self,
+            to: AccountId,
+            punk_index: u32,
+            memo: Vec<u8>,
+            payment: Balance,
+        ) -> Result<()> {
+            if payment < self.transfer_fee {
+                return Err(Error::InsufficientPayment {
+                    expected: self.transfer_fee,
+                    actual: payment,
+                });
+            }
+            self.move_punk(to, punk_index, memo)?;
+            if self.transfer_fee > 0 {
+                self.credit_pending(self.owner, self.transfer_fee);
+            }
+            if payment > self.transfer_fee {
+                self.credit_refund(self.env().caller(), payment - self.transfer_fee);
+            }
+            Ok(())
+        }
+
+        /// Sets the fee required to transfer a punk. Only callable by the owner.
+        #[ink(message)]
+        pub fn set_transfer_fee(&mut self, fee: Balance) -> Result<()> {
+            self.require_owner()?;
+            self.transfer_fee = fee;
+            Ok(())
+        }
+
+        /// Returns the fee required to transfer a punk.
         #[ink(message)]
-        pub fn transfer_punk(&mut self, to: AccountId, punk_index: u32) {
+        pub fn transfer_fee(&self) -> Balance {
+            self.transfer_fee
+        }
+
+        fn move_punk(&mut self, to: AccountId, punk_index: u32, memo: Vec<u8>) -> Result<()> {
+            let caller = self.env().caller();
             let owner = self
                 .punk_index_to_address
                 .get(punk_index)
-                .expect("Punk is not assigned");
-            assert_eq!(owner, self.env().caller());
+                .ok_or(Error::PunkNotAssigned)?;
+            if owner != caller {
+                return Err(Error::NotPunkOwner);
+            }
+            if to == caller {
+                return Err(Error::SelfTransfer);
+            }
+            if to == AccountId::from([0x0; 32]) {
+                return Err(Error::ZeroAddress);
+            }
+            self.ensure_hold_period_passed(punk_index)?;
+            if self.offer_seller(punk_index).is_some() {
+                self.no_longer_for_sale(punk_index);
+            }
             self.punk_index_to_address.insert(punk_index, &to);
-            let holder_balance = self
-                .balance_of
-                .get(self.env().caller())
-                .expect("Holder has at least 1 punk");
-            self.balance_of
-                .insert(self.env().caller(), &(holder_balance - 1));
-            let receiver_balance = self.balance_of.get(to).unwrap_or(0);
-            self.balance_of.insert(to, &(receiver_balance + 1));
+            self.last_transfer_block
+                .insert(punk_index, &self.env().block_number());
+            self.held_off_market.remove(punk_index);
+            self.decrease_balance(caller, 1);
+            self.increase_balance(to, 1);
+            let seq = self.next_event_seq();
             self.env().emit_event(Transfer {
-                from: self.env().caller(),
+                from: caller,
                 to,
                 value: 1,
+                seq,
             });
+            let seq = self.next_event_seq();
             self.env().emit_event(PunkTransfer {
-                from: self.env().caller(),
+                from: caller,
                 to,
                 punk_index,
+                memo,
+                seq,
             });
+            Ok(())
         }
 
+        /// Offers the caller's punk for sale, optionally only to `address`. Fails
+        /// with `PunkAlreadyListed` if the punk is already offered.
         #[ink(message)]
         pub fn offer_punk_for_sale(
             &mut self,
             punk_index: u32,
             min_sale_price: Balance,
             address: Option<AccountId>,
-        ) {
-            assert_eq!(
-                self.punk_index_to_address.get(punk_index),
-                Some(self.env().caller())
-            );
-            let offer = Offer {
+        ) -> Result<()> {
+            if self.punk_index_to_address.get(punk_index) != Some(self.env().caller()) {
+                return Err(Error::NotPunkOwner);
+            }
+            self.ensure_hold_period_passed(punk_index)?;
+            if self.offer_seller(punk_index).is_some() {
+                return Err(Error::PunkAlreadyListed);
+            }
+            let offers = self.active_offers_of.get(self.env().caller()).unwrap_or(0);
+            self.active_offers_of
+                .insert(self.env().caller(), &(offers + 1));
+            let offer = StoredOffer {
                 is_for_sale: true,
-                punk_index,
                 seller: self.env().caller(),
                 min_value: min_sale_price,
                 only_sell_to: address,
             };
             self.punks_offered_for_sale.insert(punk_index, &offer);
+            self.held_off_market.remove(punk_index);
+            if !self.for_sale_position.contains(punk_index) {
+                self.for_sale_indices
+                    .insert(self.for_sale_count, &punk_index);
+                self.for_sale_position
+                    .insert(punk_index, &self.for_sale_count);
+                self.for_sale_count += 1;
+            }
+            let seq = self.next_event_seq();
             self.env().emit_event(PunkOffered {
                 punk_index,
                 min_sale_price,
                 address,
+                created_at: self.env().block_timestamp(),
+                seq,
             });
+            Ok(())
         }
 
-        #[ink(message, payable)]
-        pub fn buy_punk(&mut self, punk_index: u32) {
-            let balance = self.env().transferred_value();
-            let offer = self
-                .punks_offered_for_sale
-                .get(punk_index)
-                .expect("Punk doesn't exist!");
-            assert!(offer.is_for_sale, "Punk isn't for sale!");
-            if offer.only_sell_to.is_some() {
-                assert_eq!(
-                    offer.only_sell_to,
-                    Some(self.env().caller()),
-                    "Punk is reserved for other buyer!"
-                );
-            };
-
-            assert!(balance >= offer.min_value, "Offer for punk is too low!");
-            assert_eq!(
-                self.punk_index_to_address.get(punk_index),
-                Some(offer.seller),
-                "Seller is no longer owner of the punk!"
-            );
-
-            Self::env().emit_event(Transfer {
-                from: offer.seller,
-                to: self.env().caller(),
-                value: balance,
-            });
-
-            self.pending_withdrawals.insert(offer.seller, &balance);
-
+        /// Replaces the active offer of the caller's punk. The old listing is
+        /// closed with `PunkNoLongerForSale` before the new one is announced with
+        /// `PunkOffered`.
+        #[ink(message)]
+        pub fn relist(
+            &mut self,
+            punk_index: u32,
+            min_sale_price: Balance,
+            address: Option<AccountId>,
+        ) -> Result<()> {
+            if self.punk_index_to_address.get(punk_index) != Some(self.env().caller()) {
+                return Err(Error::NotPunkOwner);
+            }
+            if self.offer_seller(punk_index).is_none() {
+                return Err(Error::PunkNotForSale);
+            }
             self.no_longer_for_sale(punk_index);
+            self.offer_punk_for_sale(punk_index, min_sale_price, address)
         }
 
-        fn no_longer_for_sale(&mut self, punk_index: u32) {
-            let offer = Offer {
-                is_for_sale: false,
+        /// Sets the number of blocks a claimed punk has to be held before it can be
+        /// transferred or offered for sale. Only callable by the owner.
+        #[ink(message)]
+        pub fn set_min_hold_blocks(&mut self, blocks: BlockNumber) -> Result<()> {
+            self.require_owner()?;
+            self.min_hold_blocks = blocks;
+            Ok(())
+        }
+
+        /// Returns the number of blocks a claimed punk has to be held.
+        #[ink(message)]
+        pub fn min_hold_blocks(&self) -> BlockNumber {
+            self.min_hold_blocks
+        }
+
+        /// Sets the number of blocks new credits are locked before they can be
+        /// withdrawn, at most `MAX_WITHDRAWAL_DELAY_BLOCKS`. Funds credited before
+        /// keep their lock. Only callable by the owner.
+        #[ink(message)]
+        pub fn set_withdrawal_delay_blocks(&mut self, blocks: BlockNumber) -> Result<()> {
+            self.require_owner()?;
+            if blocks > MAX_WITHDRAWAL_DELAY_BLOCKS {
+                return Err(Error::WithdrawalDelayTooLong);
+            }
+            self.withdrawal_delay_blocks = blocks;
+            Ok(())
+        }
+
+        /// Returns the block from which all of `account`'s pending balance can be
+        /// withdrawn, or 0 if nothing is locked.
+        #[ink(message)]
+        pub fn withdrawable_at(&self, account: AccountId) -> BlockNumber {
+            self.locked_credits(account)
+                .last()
+                .map_or(0, |&(_, unlocked_at)| unlocked_at)
+        }
+
+        /// Returns the part of `account`'s pending balance it can withdraw now.
+        #[ink(message)]
+        pub fn withdrawable_amount(&self, account: AccountId) -> Balance {
+            self.pending_of(account) - self.locked_of(account)
+        }
+
+        fn locked_of(&self, account: AccountId) -> Balance {
+            self.locked_credits(account)
+                .iter()
+                .fold(0, |locked, &(amount, _)| locked.saturating_add(amount))
+        }
+
+        /// Returns the credits of `account` which are still locked, oldest first.
+        fn locked_credits(&self, account: AccountId) -> Vec<LockedCredit> {
+            let now = self.env().block_number();
+            let mut credits = self.locked_withdrawals.get(account).unwrap_or_default();
+            credits.retain(|&(_, unlocked_at)| now < unlocked_at);
+            credits
+        }
+
+        /// Locks a new credit of `amount` for the withdrawal delay. Each credit
+        /// keeps its own unlock block, so earlier credits mature on time. Once
+        /// `MAX_LOCKED_CREDITS` are locked, the credit joins the newest one,
+        /// which delays it by at most twice the withdrawal delay.
+        fn lock_credit(&mut self, account: AccountId, amount: Balance) {
+            if self.withdrawal_delay_blocks == 0 || amount == 0 {
+                return;
+            }
+            let unlocked_at = self
+                .env()
+                .block_number()
+                .saturating_add(self.withdrawal_delay_blocks);
+            let mut credits = self.locked_credits(account);
+            let is_full = credits.len() >= MAX_LOCKED_CREDITS;
+            match credits.last_mut() {
+                Some(newest) if is_full || newest.1 == unlocked_at => {
+                    *newest = (newest.0.saturating_add(amount), unlocked_at);
+                }
+                _ => credits.push((amount, unlocked_at)),
+            }
+            self.locked_withdrawals.insert(account, &credits);
+        }
+
+        /// Pauses or resumes buying punks. Listing and delisting stay possible.
+        /// Emits `Paused` or `Unpaused` if the state changes. Only callable by the
+        /// owner.
+        #[ink(message)]
+        pub fn set_buying_paused(&mut self, paused: bool) -> Result<()> {
+            self.require_owner()?;
+            if paused == self.buying_paused {
+                return Ok(());
+            }
+            self.buying_paused = paused;
+            let by = self.env().caller();
+            let seq = self.next_event_seq();
+            if paused {
+                self.env().emit_event(Paused { by, seq });
+            } else {
+                self.env().emit_event(Unpaused { by, seq });
+            }
+            Ok(())
+        }
+
+        /// Returns whether the contract is paused, i.e. buying punks is paused.
+        /// Same as `buying_paused`.
+        #[ink(message)]
+        pub fn is_paused(&self) -> bool {
+            self.buying_paused
+        }
+
+        /// Returns whether buying punks is paused.
+        #[ink(message)]
+        pub fn buying_paused(&self) -> bool {
+            self.buying_paused
+        }
+
+        fn ensure_hold_period_passed(&self, punk_index: u32) -> Result<()> {
+            let claimed_at = self.claimed_at.get(punk_index).unwrap_or(0);
+            if self.env().block_number() < claimed_at.saturating_add(self.min_hold_blocks) {
+                return Err(Error::HoldPeriodActive);
+            }
+            Ok(())
+        }
+
+        /// Buys the offered punk at its price, which is credited to the seller, and
+        /// transfers it to the caller. Any value paid beyond the price is credited
+        /// to the caller's refunds.
+        ///
+        /// Returning an error reverts the call, so the transferred value goes back
+        /// to the buyer, e.g. when a concurrent buyer got the punk first.
+        #[ink(message, payable)]
+        pub fn buy_punk(&mut self, punk_index: u32) -> Result<()> {
+            let balance = self.env().transferred_value();
+            let buyer = self.env().caller();
+            let offer = self.check_buy(punk_index, buyer, balance)?;
+            self.settle_purchase(punk_index, offer.seller, offer.min_value);
+            if balance > offer.min_value {
+                self.credit_refund(buyer, balance - offer.min_value);
+            }
+            Ok(())
+        }
+
+        /// Buys all given punks at their offered prices in one call. The
+        /// transferred value has to cover the sum of the prices; the leftover is
+        /// credited to the caller's refunds.
+        ///
+        /// If any of the purchases fails the whole batch is reverted.
+        #[ink(message, payable)]
+        pub fn buy_punks(&mut self, punk_indices: Vec<u32>) -> Result<()> {
+            let buyer = self.env().caller();
+            let balance = self.env().transferred_value();
+            let mut total: Balance = 0;
+            for &punk_index in &punk_indices {
+                let offer = self.punk_offer(punk_index).ok_or(Error::PunkNotForSale)?;
+                total = total
+                    .checked_add(offer.min_value)
+                    .ok_or(Error::PriceOverflow)?;
+            }
+            if balance < total {
+                return Err(Error::InsufficientPayment {
+                    expected: total,
+                    actual: balance,
+                });
+            }
+            for punk_index in punk_indices {
+                let price = self
+                    .punk_offer(punk_index)
+                    .ok_or(Error::PunkNotForSale)?
+                    .min_value;
+                let offer = self.check_buy(punk_index, buyer, price)?;
+                self.settle_purchase(punk_index, offer.seller, price);
+            }
+            if balance > total {
+                self.credit_refund(buyer, balance - total);
+            }
+            Ok(())
+        }
+
+        /// Pays `price` from the caller to the seller of the checked offer, hands
+        /// the punk over and takes it off the market.
+        fn settle_purchase(&mut self, punk_index: u32, seller: AccountId, price: Balance) {
+            let buyer = self.env().caller();
+            self.punk_index_to_address.insert(punk_index, &buyer);
+            self.last_transfer_block
+                .insert(punk_index, &self.env().block_number());
+            self.decrease_balance(seller, 1);
+            self.increase_balance(buyer, 1);
+            let seq = self.next_event_seq();
+            Self::env().emit_event(Transfer {
+                from: seller,
+                to: buyer,
+                value: price,
+                seq,
+            });
+            let seq = self.next_event_seq();
+            Self::env().emit_event(PunkTransfer {
+                from: seller,
+                to: buyer,
                 punk_index,
-                seller: self.env().caller(),
-                min_value: 0,
-                only_sell_to: None,
-            };
-            self.punks_offered_for_sale.insert(punk_index, &offer);
-            Self::env().emit_event(PunkNoLongerForSale { punk_index });
+                memo: Vec::new(),
+                seq,
+            });
+
+            if price > 0 {
+                self.credit_pending(seller, price);
+            }
+
+            let spent = self.total_spent.get(buyer).unwrap_or(0);
+            self.total_spent.insert(buyer, &spent.saturating_add(price));
+            let earned = self.total_earned.get(seller).unwrap_or(0);
+            self.total_earned
+                .insert(seller, &earned.saturating_add(price));
+
+            self.no_longer_for_sale(punk_index);
+        }
+
+        /// Runs all checks of `buy_punk` for `buyer` paying `value` without buying,
+        /// so wallets can find out beforehand whether the purchase would succeed.
+        #[ink(message)]
+        pub fn can_buy(&self, punk_index: u32, buyer: AccountId, value: Balance) -> Result<()> {
+            self.check_buy(punk_index, buyer, value).map(|_| ())
+        }
+
+        /// Returns whether the punk's active offer may be bought by `who`, i.e.
+        /// whether it is open to anyone or reserved for `who`.
+        #[ink(message)]
+        pub fn can_address_buy(&self, punk_index: u32, who: AccountId) -> bool {
+            self.punk_offer(punk_index)
+                .is_some_and(|offer| offer.only_sell_to.is_none_or(|buyer| buyer == who))
+        }
+
+        fn check_buy(&self, punk_index: u32, buyer: AccountId, value: Balance) -> Result<Offer> {
+            if self.buying_paused {
+                return Err(Error::BuyingPaused);
+            }
+            let offer = self
+                .punks_offered_for_sale
+                .get(punk_index)
+                .ok_or(Error::PunkNotForSale)?;
+            if !offer.is_for_sale {
+                return Err(Error::PunkNotForSale);
+            }
+            if offer.only_sell_to.is_some() && offer.only_sell_to != Some(buyer) {
+                return Err(Error::PunkReservedForOtherBuyer);
+            }
+            if value < offer.min_value {
+                return Err(Error::InsufficientPayment {
+                    expected: offer.min_value,
+                    actual: value,
+                });
+            }
+            if self.punk_index_to_address.get(punk_index) != Some(offer.seller) {
+                return Err(Error::SellerNotOwner);
+            }
+            Ok(offer.with_index(punk_index))
+        }
+
+        /// Offers the caller's punk to `recipient` for free. The recipient claims it
+        /// with `claim_gift`, so the giver doesn't pay for the transfer.
+        #[ink(message)]
+        pub fn gift_to(&mut self, punk_index: u32, recipient: AccountId) -> Result<()> {
+            if recipient == self.env().caller() {
+                return Err(Error::SelfTransfer);
+            }
+            if recipient == AccountId::from([0x0; 32]) {
+                return Err(Error::ZeroAddress);
+            }
+            self.offer_punk_for_sale(punk_index, 0, Some(recipient))
+        }
+
+        /// Claims a punk gifted to the caller with `gift_to`.
+        ///
+        /// Like sales, gifts deliberately don't charge the transfer fee: the
+        /// zero-price offer reserved to the recipient could be bought for
+        /// nothing with `buy_punk` anyway.
+        #[ink(message)]
+        pub fn claim_gift(&mut self, punk_index: u32) -> Result<()> {
+            let caller = self.env().caller();
+            let offer = self.check_buy(punk_index, caller, 0)?;
+            if offer.only_sell_to != Some(caller) {
+                return Err(Error::PunkReservedForOtherBuyer);
+            }
+            self.settle_purchase(punk_index, offer.seller, 0);
+            Ok(())
+        }
+
+        /// Buys the punk like `buy_punk`, but only if it is still offered at
+        /// `expected_price`. Protects buyers against a price change between
+        /// reading the offer and buying.
+        #[ink(message, payable)]
+        pub fn buy_punk_at(&mut self, punk_index: u32, expected_price: Balance) -> Result<()> {
+            let offer = self.punk_offer(punk_index).ok_or(Error::PunkNotForSale)?;
+            if offer.min_value != expected_price {
+                return Err(Error::PriceChanged);
+            }
+            self.buy_punk(punk_index)
+        }
+
+        /// Takes all given punks of the caller off the market. Indices which are
+        /// not owned by the caller or not offered for sale are skipped.
+        #[ink(message)]
+        pub fn cancel_all_offers(&mut self, punk_indices: Vec<u32>) {
+            let caller = self.env().caller();
+            for punk_index in punk_indices {
+                let is_owner = self.punk_index_to_address.get(punk_index) == Some(caller);
+                let is_for_sale = self
+                    .punks_offered_for_sale
+                    .get(punk_index)
+                    .is_some_and(|offer| offer.is_for_sale);
+                if is_owner && is_for_sale {
+                    self.no_longer_for_sale(punk_index);
+                }
+            }
+        }
+
+        /// Returns the punk's active offer, if it is for sale.
+        #[ink(message)]
+        pub fn punk_offer(&self, punk_index: u32) -> Option<Offer> {
+            self.punks_offered_for_sale
+                .get(punk_index)
+                .filter(|offer| offer.is_for_sale)
+                .map(|offer| offer.with_index(punk_index))
+        }
+
+        /// Returns the punk's last recorded offer, including a disabled one after
+        /// a sale or cancellation. `None` if the punk was never listed.
+        #[ink(message)]
+        pub fn offer_history(&self, punk_index: u32) -> Option<Offer> {
+            self.punks_offered_for_sale
+                .get(punk_index)
+                .map(|offer| offer.with_index(punk_index))
+        }
+
+        /// Returns the seller of the punk's active offer, if it is for sale.
+        #[ink(message)]
+        pub fn offer_seller(&self, punk_index: u32) -> Option<AccountId> {
+            self.punks_offered_for_sale
+                .get(punk_index)
+                .filter(|offer| offer.is_for_sale)
+                .map(|offer| offer.seller)
+        }
+
+        /// Returns the buyer the punk's active offer is reserved for, if any.
+        #[ink(message)]
+        pub fn offer_reserved_for(&self, punk_index: u32) -> Option<AccountId> {
+            self.punk_offer(punk_index)
+                .and_then(|offer| offer.only_sell_to)
+        }
+
+        /// Returns whether the punk has an active offer whose seller still owns it.
+        /// Guards against stale offers whose seller no longer owns the punk.
+        #[ink(message)]
+        pub fn is_offer_valid(&self, punk_index: u32) -> bool {
+            self.offer_seller(punk_index)
+                .is_some_and(|seller| self.punk_index_to_address.get(punk_index) == Some(seller))
+        }
+
+        /// Takes any punk off the market regardless of its seller, e.g. to delist
+        /// stolen punks. Only callable by the owner.
+        #[ink(message)]
+        pub fn force_cancel_offer(&mut self, punk_index: u32) -> Result<()> {
+            self.require_owner()?;
+            if self.offer_seller(punk_index).is_none() {
+                return Err(Error::PunkNotForSale);
+            }
+            self.no_longer_for_sale(punk_index);
+            Ok(())
+        }
+
+        /// Takes the caller's punk off the market and marks it as deliberately not
+        /// for sale, instead of listing it at an unreachable price. Offering the
+        /// punk for sale again clears the mark.
+        #[ink(message)]
+        pub fn reserve_punk(&mut self, punk_index: u32) -> Result<()> {
+            if self.punk_index_to_address.get(punk_index) != Some(self.env().caller()) {
+                return Err(Error::NotPunkOwner);
+            }
+            if self
+                .punks_offered_for_sale
+                .get(punk_index)
+                .is_some_and(|offer| offer.is_for_sale)
+            {
+                self.no_longer_for_sale(punk_index);
+            }
+            self.held_off_market.insert(punk_index, &());
+            Ok(())
+        }
+
+        /// Returns whether the holder marked the punk as not for sale with
+        /// `reserve_punk`. Punks reserved to the owner aren't held off market.
+        #[ink(message)]
+        pub fn is_held_off_market(&self, punk_index: u32) -> bool {
+            self.held_off_market.contains(punk_index)
+        }
+
+        fn no_longer_for_sale(&mut self, punk_index: u32) {
+            if let Some(seller) = self.offer_seller(punk_index) {
+                self.remove_active_offer(seller);
+            }
+            if let Some(mut offer) = self.punks_offered_for_sale.get(punk_index) {
+                offer.is_for_sale = false;
+                self.punks_offered_for_sale.insert(punk_index, &offer);
+            }
+            if let Some(position) = self.for_sale_position.take(punk_index) {
+                // Moves the last listing into the freed position.
+                self.for_sale_count -= 1;
+                let last = self.for_sale_count;
+                let last_punk = self
+                    .for_sale_indices
+                    .take(last)
+                    .expect("Listing positions are contiguous");
+                if position != last {
+                    self.for_sale_indices.insert(position, &last_punk);
+                    self.for_sale_position.insert(last_punk, &position);
+                }
+            }
+            let seq = self.next_event_seq();
+            Self::env().emit_event(PunkNoLongerForSale { punk_index, seq });
+        }
+
+        fn remove_active_offer(&mut self, seller: AccountId) {
+            let offers = self.active_offers_of.get(seller).unwrap_or(0);
+            self.active_offers_of
+                .insert(seller, &offers.saturating_sub(1));
+        }
+
+        /// Returns the number of punks `owner` currently offers for sale.
+        #[ink(message)]
+        pub fn active_offers_count(&self, owner: AccountId) -> u32 {
+            self.active_offers_of.get(owner).unwrap_or(0)
+        }
+
+        /// Returns all punks offered for sale together with their minimum price.
+        ///
+        /// Reads one offer per listing, so the gas cost grows with the number of
+        /// punks for sale.
+        #[ink(message)]
+        pub fn punks_for_sale(&self) -> Vec<(u32, Balance)> {
+            self.listed_punks(0, self.for_sale_count)
+                .filter_map(|punk_index| {
+                    self.punks_offered_for_sale
+                        .get(punk_index)
+                        .map(|offer| (punk_index, offer.min_value))
+                })
+                .collect()
+        }
+
+        /// Returns up to `limit` punks offered for sale, starting at position
+        /// `start` of the listings, together with the cursor of the next page, if
+        /// any. Bounds the gas cost of enumerating many listings. A `limit` of 0
+        /// returns an empty page without a cursor.
+        #[ink(message)]
+        pub fn punks_for_sale_page(
+            &self,
+            start: u32,
+            limit: u32,
+        ) -> (Vec<(u32, Balance)>, Option<u32>) {
+            let page = self
+                .listed_punks(start, limit)
+                .filter_map(|punk_index| {
+                    self.punks_offered_for_sale
+                        .get(punk_index)
+                        .map(|offer| (punk_index, offer.min_value))
+                })
+                .collect();
+            let end = start.saturating_add(limit);
+            let next_cursor = (limit > 0 && end < self.for_sale_count).then_some(end);
+            (page, next_cursor)
+        }
+
+        /// Returns the punks `seller` offers for sale together with their minimum
+        /// price. Scans all listings, like `punks_for_sale`.
+        #[ink(message)]
+        pub fn offers_by(&self, seller: AccountId) -> Vec<(u32, Balance)> {
+            self.listed_punks(0, self.for_sale_count)
+                .filter_map(|punk_index| {
+                    self.punks_offered_for_sale
+                        .get(punk_index)
+                        .filter(|offer| offer.seller == seller)
+                        .map(|offer| (punk_index, offer.min_value))
+                })
+                .collect()
+        }
+
+        /// Returns up to `limit` listed punk indices, starting at position `start`.
+        fn listed_punks(&self, start: u32, limit: u32) -> impl Iterator<Item = u32> + '_ {
+            let end = start.saturating_add(limit).min(self.for_sale_count);
+            (start..end).filter_map(|position| self.for_sale_indices.get(position))
+        }
+
+        /// Returns the lowest minimum price of all punks offered for sale, `None`
+        /// if no punk is for sale. Scans all listings, like `punks_for_sale`.
+        #[ink(message)]
+        pub fn floor_price(&self) -> Option<Balance> {
+            self.punks_for_sale()
+                .into_iter()
+                .map(|(_, min_value)| min_value)
+                .min()
+        }
+
+        /// Returns `(total_assigned, number_for_sale, floor_price, holder_count)` in
+        /// one call, with a floor price of 0 if no punk is for sale.
+        #[ink(message)]
+        pub fn stats(&self) -> (u32, u32, Balance, u32) {
+            (
+                self.total_supply - self.punks_remaining_to_assign,
+                self.for_sale_count,
+                self.floor_price().unwrap_or(0),
+                self.unique_holders,
+            )
+        }
+
+        /// Withdraws the caller's whole pending balance, both earnings and refunds,
+        /// except for recent credits still locked by the withdrawal delay.
+        ///
+        /// Callers may be contracts, too: a balance transfer doesn't execute the
+        /// receiver's code, so it can't be refused for lack of a payable message.
+        /// Should the transfer fail for any other reason, `TransferFailed` is
+        /// returned and the funds stay claimable.
+        #[ink(message)]
+        pub fn withdraw(&mut self) -> Result<()> {
+            let caller = self.env().caller();
+            if self.pending_of(caller) == 0 {
+                return Err(Error::NoPendingWithdrawals);
+            }
+            let amount = self.withdrawable_amount(caller);
+            if amount == 0 {
+                return Err(Error::WithdrawalLocked);
+            }
+            self.pay_out(caller, amount)
+        }
+
+        /// Pays out the pending balance of `to` on their behalf, so the owner covers
+        /// the gas. Only callable by the owner.
+        #[ink(message)]
+        pub fn force_push_withdrawal(&mut self, to: AccountId) -> Result<()> {
+            self.require_owner()?;
+            if self.pending_of(to) == 0 {
+                return Err(Error::NoPendingWithdrawals);
+            }
+            let amount = self.withdrawable_amount(to);
+            if amount == 0 {
+                return Err(Error::WithdrawalLocked);
+            }
+            self.pay_out(to, amount)
         }
 
+        /// Withdraws `amount` of the caller's pending balance, leaving the rest
+        /// claimable.
         #[ink(message)]
-        pub fn withdraw(&mut self) {
+        pub fn withdraw_amount(&mut self, amount: u128) -> Result<()> {
             let caller = self.env().caller();
-            let amount = self
-                .pending_withdrawals
-                .get(caller)
-                .expect("No pending withdrawals for caller");
-            assert!(amount > 0, "No remaining balance to withdraw!");
-            self.pending_withdrawals.insert(self.env().caller(), &0);
-            self.env()
-                .transfer(caller, amount)
-                .expect("Transfer failed");
+            if amount == 0 {
+                return Err(Error::ZeroAmount);
+            }
+            if amount > self.pending_of(caller) {
+                return Err(Error::AmountExceedsPending);
+            }
+            self.pay_out(caller, amount)
+        }
+
+        /// Splits the contract's surplus, its balance not owed to pending
+        /// withdrawals, among `recipients` by their share in basis points. The
+        /// shares have to sum up to 10000. Rounding dust stays in the contract.
+        /// Only callable by the owner.
+        #[ink(message)]
+        pub fn distribute(&mut self, recipients: Vec<(AccountId, u16)>) -> Result<()> {
+            self.require_owner()?;
+            let total_bps: u32 = recipients.iter().map(|&(_, bps)| u32::from(bps)).sum();
+            if total_bps != 10000 {
+                return Err(Error::InvalidShares);
+            }
+            let surplus = self.env().balance().saturating_sub(self.total_pending);
+            for (recipient, bps) in recipients {
+                self.credit_pending(recipient, surplus * Balance::from(bps) / 10000);
+            }
+            Ok(())
+        }
+
+        fn credit_pending(&mut self, account: AccountId, amount: Balance) {
+            if amount == 0 {
+                return;
+            }
+            let pending = self.pending_withdrawals.get(account).unwrap_or(0);
+            self.pending_withdrawals
+                .insert(account, &pending.saturating_add(amount));
+            self.total_pending = self.total_pending.saturating_add(amount);
+            self.lock_credit(account, amount);
+        }
+
+        fn credit_refund(&mut self, account: AccountId, amount: Balance) {
+            if amount == 0 {
+                return;
+            }
+            let refunds = self.pending_refunds.get(account).unwrap_or(0);
+            self.pending_refunds
+                .insert(account, &refunds.saturating_add(amount));
+            self.total_pending = self.total_pending.saturating_add(amount);
+            self.lock_credit(account, amount);
+        }
+
+        /// Returns the earnings and refunds `account` can withdraw.
+        fn pending_of(&self, account: AccountId) -> Balance {
+            self.pending_withdrawals
+                .get(account)
+                .unwrap_or(0)
+                .saturating_add(self.pending_refunds.get(account).unwrap_or(0))
+        }
+
+        /// Pays out `amount` of the pending balance of `to` if it is unlocked.
+        /// Every payout is refused while the contract is insolvent.
+        fn pay_out(&mut self, to: AccountId, amount: Balance) -> Result<()> {
+            if amount > self.withdrawable_amount(to) {
+                return Err(Error::WithdrawalLocked);
+            }
+            if !self.is_solvent() {
+                return Err(Error::Insolvent);
+            }
+            self.transfer_pending(to, amount)
+        }
+
+        /// Transfers `amount` of the pending balance of `to` to them, drawing on
+        /// the refunds first. The amount is debited before transferring, so a
+        /// reentrant call can't pay it out twice, and restored if the transfer
+        /// fails, so it stays claimable.
+        fn transfer_pending(&mut self, to: AccountId, amount: Balance) -> Result<()> {
+            let refunds = self.pending_refunds.get(to).unwrap_or(0);
+            let from_refunds = amount.min(refunds);
+            let from_earnings = amount - from_refunds;
+            let earnings = self.pending_withdrawals.get(to).unwrap_or(0);
+            self.pending_refunds.insert(to, &(refunds - from_refunds));
+            self.pending_withdrawals
+                .insert(to, &(earnings - from_earnings));
+            self.total_pending -= amount;
+            if self.env().transfer(to, amount).is_err() {
+                self.pending_refunds.insert(to, &refunds);
+                self.pending_withdrawals.insert(to, &earnings);
+                self.total_pending += amount;
+                return Err(Error::TransferFailed);
+            }
+            Ok(())
+        }
+
+        /// Returns the refunds of overpayments `account` can withdraw.
+        #[ink(message)]
+        pub fn refunds_of(&self, account: AccountId) -> Balance {
+            self.pending_refunds.get(account).unwrap_or(0)
+        }
+
+        /// Returns the earnings, e.g. sale proceeds, `account` can withdraw.
+        #[ink(message)]
+        pub fn earnings_of(&self, account: AccountId) -> Balance {
+            self.pending_withdrawals.get(account).unwrap_or(0)
+        }
+
+        /// Returns whether the contract balance covers all pending withdrawals.
+        /// Payouts are refused while it doesn't.
+        #[ink(message)]
+        pub fn is_solvent(&self) -> bool {
+            self.env().balance() >= self.total_pending
+        }
+
+        /// Terminates the contract and sends its remaining balance and storage
+        /// deposit to the owner. Only possible while no withdrawals are pending,
+        /// so no user funds are stranded. Only callable by the owner.
+        #[ink(message)]
+        pub fn terminate(&mut self) -> Result<()> {
+            self.require_owner()?;
+            if self.total_pending > 0 {
+                return Err(Error::PendingWithdrawalsExist);
+            }
+            self.env().terminate_contract(self.owner)
+        }
+
+        /// Returns whether `who` has funds to withdraw.
+        #[ink(message)]
+        pub fn has_pending(&self, who: AccountId) -> bool {
+            self.pending_of(who) > 0
+        }
+
+        /// Returns the total amount `account` has paid for punks.
+        #[ink(message)]
+        pub fn spent_by(&self, account: AccountId) -> Balance {
+            self.total_spent.get(account).unwrap_or(0)
+        }
+
+        /// Returns the total amount `account` has been credited for sold punks.
+        #[ink(message)]
+        pub fn earned_by(&self, account: AccountId) -> Balance {
+            self.total_earned.get(account).unwrap_or(0)
         }
     }
 
     /// Unit tests in Rust are normally defined within such a `#[cfg(test)]`
     /// module and test functions are marked with a `#[test]` attribute.
     /// The below code is technically just normal Rust code.
-    #[cfg(feature = "ink-experimental-engine")]
     #[cfg(test)]
     mod tests {
         /// Imports all the definitions from the outer scope so we can use them here.
         use super::*;
 
-        /// Imports `ink_lang` so we can use `#[ink::test]`.
-        use ink_lang as ink;
+        type Event = <Cryptopunks as ::ink::reflect::ContractEventBase>::Type;
 
         // Helper Function to set the execution context for the next Contract Call
         fn set_sender(sender: AccountId) {
-            ink_env::test::set_caller::<ink_env::DefaultEnvironment>(sender);
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(sender);
         }
 
         // Helper Function to set the execution context for the next Contract Call
         fn set_balance(sender: AccountId, amount: Balance) {
-            ink_env::test::set_account_balance::<ink_env::DefaultEnvironment>(sender, amount)
+            ink::env::test::set_account_balance::<ink::env::DefaultEnvironment>(sender, amount)
+        }
+
+        // Helper Function to decode all events emitted so far
+        fn recorded_events() -> Vec<Event> {
+            ink::env::test::recorded_events()
+                .map(|event| {
+                    <Event as scale::Decode>::decode(&mut &event.data[..])
+                        .expect("Invalid event data")
+                })
+                .collect()
+        }
+
+        // Helper Function to advance the block number by `blocks` blocks
+        fn advance_blocks(blocks: u32) {
+            for _ in 0..blocks {
+                ink::env::test::advance_block::<ink::env::DefaultEnvironment>();
+            }
         }
 
         /// We test if the default constructor does its job.
@@ -278,48 +1716,2133 @@ mod cryptopunks {
             let _cryptopunks = Cryptopunks::new();
         }
 
-        // We test if Alice can obtain an available Punk
+        // We test if the default constructor uses the Substrate token decimals
         #[ink::test]
-        fn get_works() {
-            let mut cryptopunks = Cryptopunks::new();
-            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>();
-            let _balance =
-                ink_env::test::get_account_balance::<ink_env::DefaultEnvironment>(accounts.alice)
-                    .expect("Alice has no Account Balance");
-            cryptopunks.get_punk(0);
+        fn decimals_works() {
+            let cryptopunks = Cryptopunks::new();
+            assert_eq!(cryptopunks.decimals(), 12);
+            let cryptopunks = Cryptopunks::new_with_decimals(18);
+            assert_eq!(cryptopunks.decimals(), 18);
         }
 
-        // We test if Alice can obtain a Punk and sell it to Charlie
+        // We test if the contract's own account id is returned
         #[ink::test]
-        fn sale_works() {
+        fn contract_account_works() {
+            let cryptopunks = Cryptopunks::new();
+            assert_ne!(cryptopunks.contract_account(), AccountId::from([0x0; 32]));
+            assert_eq!(
+                cryptopunks.contract_account(),
+                ink::env::test::callee::<ink::env::DefaultEnvironment>()
+            );
+        }
+
+        // We test if the reserved punks of the owner are accounted for
+        #[ink::test]
+        fn number_of_punks_reserved_works() {
             let mut cryptopunks = Cryptopunks::new();
+            assert_eq!(cryptopunks.number_of_punks_reserved(), 0);
+            assert_eq!(cryptopunks.reserve_punks_for_owner(7), Ok(7));
+            assert_eq!(cryptopunks.number_of_punks_reserved(), 7);
+            assert_eq!(cryptopunks.number_of_punks_to_reserve(), 1000);
 
-            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>();
-            let balance =
-                ink_env::test::get_account_balance::<ink_env::DefaultEnvironment>(accounts.alice)
-                    .expect("Alice has no Account Balance");
+            cryptopunks.assert_invariants();
+        }
 
-            set_sender(accounts.alice);
+        // We test if unassigned punks count as reservable and claimed ones as assigned
+        #[ink::test]
+        fn supply_breakdown_works() {
+            let mut cryptopunks = Cryptopunks::new();
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            assert_eq!(cryptopunks.supply_breakdown(), (1000, 0, 0));
 
-            cryptopunks.get_punk(0);
+            assert_eq!(cryptopunks.reserve_punks_for_owner(300), Ok(300));
+            assert_eq!(cryptopunks.supply_breakdown(), (700, 0, 300));
 
-            cryptopunks.offer_punk_for_sale(0, 100000, None);
+            set_sender(accounts.bob);
+            assert_eq!(cryptopunks.get_punk(500), Ok(()));
+            let (reservable, mintable, assigned) = cryptopunks.supply_breakdown();
+            assert_eq!((reservable, assigned), (699, 301));
+            assert_eq!(reservable + mintable + assigned, cryptopunks.total_supply);
 
-            set_sender(accounts.charlie);
-            set_balance(accounts.charlie, 200000);
-            ink_env::test::set_value_transferred::<ink_env::DefaultEnvironment>(100000);
+            cryptopunks.assert_invariants();
+        }
+
+        // We test if a reservation run against a nearly exhausted supply stops early
+        #[ink::test]
+        fn reserve_punks_for_owner_stops_at_supply() {
+            let mut cryptopunks = Cryptopunks::new();
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
 
-            cryptopunks.buy_punk(0);
+            assert_eq!(cryptopunks.reserve_punks_for_owner(995), Ok(995));
+            set_sender(accounts.bob);
+            assert_eq!(cryptopunks.get_punk(997), Ok(()));
 
             set_sender(accounts.alice);
+            assert_eq!(cryptopunks.reserve_punks_for_owner(10), Ok(4));
+            assert_eq!(cryptopunks.owner_of(997), Some(accounts.bob));
+            assert_eq!(cryptopunks.owner_of(999), Some(accounts.alice));
+            assert!(cryptopunks.is_sold_out());
+            assert_eq!(cryptopunks.reserve_punks_for_owner(10), Ok(0));
 
-            cryptopunks.withdraw();
+            cryptopunks.assert_invariants();
+        }
 
-            let new_balance =
-                ink_env::test::get_account_balance::<ink_env::DefaultEnvironment>(accounts.alice)
-                    .expect("Alice has no Account Balance");
+        // We test if a reservation run never goes beyond the reservation quota
+        #[ink::test]
+        fn reserve_punks_for_owner_stops_at_quota() {
+            let mut cryptopunks = Cryptopunks::new();
+            assert_eq!(cryptopunks.reserve_punks_for_owner(400), Ok(400));
+            assert_eq!(cryptopunks.reserve_punks_for_owner(u32::MAX), Ok(600));
+            assert_eq!(cryptopunks.number_of_punks_reserved(), 1000);
+            assert_eq!(cryptopunks.owner_reservable_remaining(), 0);
+            assert_eq!(cryptopunks.config().1, 0);
+
+            cryptopunks.assert_invariants();
+        }
+
+        // We test if the remaining reservable punks shrink with each reservation
+        #[ink::test]
+        fn owner_reservable_remaining_works() {
+            let mut cryptopunks = Cryptopunks::new();
+            assert_eq!(cryptopunks.owner_reservable_remaining(), 1000);
+            assert_eq!(cryptopunks.reserve_punks_for_owner(300), Ok(300));
+            assert_eq!(cryptopunks.owner_reservable_remaining(), 700);
+
+            cryptopunks.assert_invariants();
+        }
+
+        // We test if all owner-gated messages reject a non-owner with the same error
+        #[ink::test]
+        fn owner_functions_reject_non_owner() {
+            let mut cryptopunks = Cryptopunks::new();
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+
+            set_sender(accounts.alice);
+            assert_eq!(cryptopunks.get_punk(0), Ok(()));
+            assert_eq!(cryptopunks.offer_punk_for_sale(0, 100000, None), Ok(()));
+
+            set_sender(accounts.bob);
+            let results = vec![
+                cryptopunks.seed_owner(0, accounts.bob),
+                cryptopunks.propose_owner(accounts.bob),
+                cryptopunks.cancel_owner_proposal(),
+                cryptopunks.reserve_indices(vec![1]),
+                cryptopunks.reserve_punks_for_owner(1).map(|_| ()),
+                cryptopunks.reserve_range(1, 2, false),
+                cryptopunks.set_transfer_fee(1),
+                cryptopunks.set_min_hold_blocks(1),
+                cryptopunks.set_buying_paused(true),
+                cryptopunks.set_withdrawal_delay_blocks(1),
+                cryptopunks.terminate(),
+                cryptopunks.force_cancel_offer(0),
+                cryptopunks.force_push_withdrawal(accounts.alice),
+                cryptopunks.distribute(vec![(accounts.bob, 10000)]),
+            ];
+            for result in results {
+                assert_eq!(result, Err(Error::NotOwner));
+            }
+            assert_eq!(cryptopunks.owner(), accounts.alice);
+
+            cryptopunks.assert_invariants();
+        }
+
+        // We test if a non-owner reserving punks gets a clean error
+        #[ink::test]
+        fn reserve_punks_for_owner_rejects_non_owner() {
+            let mut cryptopunks = Cryptopunks::new();
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+
+            set_sender(accounts.bob);
+            assert_eq!(cryptopunks.reserve_punks_for_owner(5), Err(Error::NotOwner));
+            assert_eq!(cryptopunks.number_of_punks_reserved(), 0);
+
+            cryptopunks.assert_invariants();
+        }
+
+        // We test if a proposed owner only takes over after accepting
+        #[ink::test]
+        fn propose_and_accept_ownership_works() {
+            let mut cryptopunks = Cryptopunks::new();
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+
+            set_sender(accounts.alice);
+            assert_eq!(cryptopunks.propose_owner(accounts.bob), Ok(()));
+            assert_eq!(cryptopunks.owner(), accounts.alice);
+            assert_eq!(cryptopunks.pending_owner(), Some(accounts.bob));
+            assert!(matches!(
+                recorded_events().last(),
+                Some(Event::OwnershipProposed(event))
+                    if event.from == accounts.alice && event.to == accounts.bob
+            ));
+
+            set_sender(accounts.charlie);
+            assert_eq!(cryptopunks.accept_ownership(), Err(Error::NotPendingOwner));
+            assert_eq!(cryptopunks.owner(), accounts.alice);
+
+            set_sender(accounts.alice);
+            assert_eq!(cryptopunks.cancel_owner_proposal(), Ok(()));
+            assert_eq!(cryptopunks.pending_owner(), None);
+            set_sender(accounts.bob);
+            assert_eq!(cryptopunks.accept_ownership(), Err(Error::NotPendingOwner));
+
+            set_sender(accounts.alice);
+            assert_eq!(cryptopunks.propose_owner(accounts.bob), Ok(()));
+
+            set_sender(accounts.bob);
+            assert_eq!(cryptopunks.accept_ownership(), Ok(()));
+            assert_eq!(cryptopunks.owner(), accounts.bob);
+            assert_eq!(cryptopunks.pending_owner(), None);
+            assert!(matches!(
+                recorded_events().last(),
+                Some(Event::OwnershipTransferred(event))
+                    if event.from == accounts.alice && event.to == accounts.bob
+            ));
+            assert_eq!(cryptopunks.accept_ownership(), Err(Error::NotPendingOwner));
+
+            cryptopunks.assert_invariants();
+        }
+
+        // We test if reservations follow a transfer of the contract ownership
+        #[ink::test]
+        fn reserve_after_ownership_transfer() {
+            let mut cryptopunks = Cryptopunks::new();
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+
+            set_sender(accounts.alice);
+            assert_eq!(cryptopunks.reserve_punks_for_owner(2), Ok(2));
+            assert_eq!(cryptopunks.propose_owner(accounts.bob), Ok(()));
+
+            set_sender(accounts.bob);
+            assert_eq!(cryptopunks.reserve_punks_for_owner(1), Err(Error::NotOwner));
+            assert_eq!(cryptopunks.accept_ownership(), Ok(()));
+            assert_eq!(cryptopunks.owner(), accounts.bob);
+            assert_eq!(cryptopunks.reserve_punks_for_owner(3), Ok(3));
+            for punk_index in 2..5 {
+                assert_eq!(cryptopunks.owner_of(punk_index), Some(accounts.bob));
+            }
+            assert_eq!(cryptopunks.number_of_punks_reserved(), 5);
+            cryptopunks.assert_invariants();
+
+            set_sender(accounts.alice);
+            assert_eq!(cryptopunks.reserve_punks_for_owner(1), Err(Error::NotOwner));
+        }
+
+        // We test if reserved punks are counted for the owner even after a transfer
+        #[ink::test]
+        fn reserved_count_of_works() {
+            let mut cryptopunks = Cryptopunks::new();
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+
+            set_sender(accounts.alice);
+            assert_eq!(cryptopunks.reserve_punks_for_owner(3), Ok(3));
+            assert_eq!(cryptopunks.reserve_indices(vec![500]), Ok(()));
+            assert_eq!(cryptopunks.get_punk(600), Ok(()));
+            assert_eq!(cryptopunks.transfer_punk(accounts.bob, 0), Ok(()));
+
+            assert_eq!(cryptopunks.reserved_count_of(accounts.alice), 4);
+            assert_eq!(cryptopunks.reserved_count_of(accounts.bob), 0);
+
+            cryptopunks.assert_invariants();
+        }
+
+        // We test if completing the reservations is signalled exactly once
+        #[ink::test]
+        fn reservation_complete_works() {
+            let mut cryptopunks = Cryptopunks::new();
+            assert_eq!(cryptopunks.reserve_punks_for_owner(600), Ok(600));
+            assert_eq!(cryptopunks.reserve_punks_for_owner(400), Ok(400));
+
+            let completions: Vec<_> = recorded_events()
+                .into_iter()
+                .filter_map(|event| match event {
+                    Event::ReservationComplete(event) => Some(event.total_reserved),
+                    _ => None,
+                })
+                .collect();
+            assert_eq!(completions, vec![1000]);
+
+            cryptopunks.assert_invariants();
+        }
+
+        // We test if a migrated contract can be seeded with the prior owners
+        #[ink::test]
+        fn migrate_works() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut cryptopunks = Cryptopunks::migrate(accounts.bob, 1000, 2);
+            assert_eq!(cryptopunks.config(), (1000, 998, 1000, 2));
+
+            assert_eq!(
+                cryptopunks.seed_owner(0, accounts.charlie),
+                Err(Error::NotOwner)
+            );
+
+            set_sender(accounts.bob);
+            assert_eq!(cryptopunks.seed_owner(0, accounts.charlie), Ok(()));
+            assert_eq!(cryptopunks.seed_owner(1, accounts.charlie), Ok(()));
+            assert_eq!(cryptopunks.owner_of(0), Some(accounts.charlie));
+            assert_eq!(cryptopunks.owner_of(2), None);
+            assert_eq!(
+                cryptopunks.seed_owner(0, accounts.django),
+                Err(Error::PunkAlreadyAssigned)
+            );
+            assert_eq!(
+                cryptopunks.seed_owner(1000, accounts.django),
+                Err(Error::PunkIndexOutOfRange)
+            );
+
+            cryptopunks.assert_invariants();
+        }
+
+        // We test if punks of a prior deployment can only be seeded, not claimed anew
+        #[ink::test]
+        fn migrated_punks_await_seeding() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut cryptopunks = Cryptopunks::migrate(accounts.alice, 10, 5);
+
+            set_sender(accounts.bob);
+            assert_eq!(cryptopunks.get_punk(3), Err(Error::PunkNotSeeded));
+            assert_eq!(cryptopunks.get_punk(7), Ok(()));
+
+            set_sender(accounts.alice);
+            assert_eq!(
+                cryptopunks.reserve_indices(vec![3]),
+                Err(Error::PunkNotSeeded)
+            );
+            assert_eq!(
+                cryptopunks.reserve_range(2, 4, false),
+                Err(Error::PunkNotSeeded)
+            );
+            for punk_index in 0..5 {
+                assert_eq!(cryptopunks.seed_owner(punk_index, accounts.charlie), Ok(()));
+            }
+            assert_eq!(cryptopunks.owner_of(3), Some(accounts.charlie));
+            assert_eq!(cryptopunks.config().1, 4);
+
+            assert_eq!(cryptopunks.seed_owner(8, accounts.charlie), Ok(()));
+            assert_eq!(cryptopunks.owner_of(8), Some(accounts.charlie));
+            assert_eq!(cryptopunks.config().1, 3);
+            assert_eq!(
+                cryptopunks.seed_owner(10, accounts.charlie),
+                Err(Error::PunkIndexOutOfRange)
+            );
+            set_sender(accounts.bob);
+            assert_eq!(cryptopunks.get_punk(8), Err(Error::PunkAlreadyAssigned));
+
+            cryptopunks.assert_invariants();
+        }
+
+        // We test if a migration can't exceed the maximum supply
+        #[ink::test]
+        #[should_panic(expected = "Total supply exceeds MAX_SUPPLY!")]
+        fn migrate_above_max_supply_fails() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let _cryptopunks = Cryptopunks::migrate(accounts.bob, MAX_SUPPLY + 1, 0);
+        }
+
+        // We test if the supply and reservation config is returned at once
+        #[ink::test]
+        fn config_works() {
+            let mut cryptopunks = Cryptopunks::new();
+            assert_eq!(cryptopunks.reserve_punks_for_owner(5), Ok(5));
+            assert_eq!(cryptopunks.config(), (1000, 995, 1000, 5));
+
+            cryptopunks.assert_invariants();
+        }
+
+        // We test if the sale is sold out after the last punk was assigned
+        #[ink::test]
+        fn is_sold_out_works() {
+            let mut cryptopunks = Cryptopunks::new();
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+
+            assert_eq!(cryptopunks.reserve_punks_for_owner(999), Ok(999));
+            assert!(!cryptopunks.is_sold_out());
+
+            set_sender(accounts.bob);
+            assert_eq!(cryptopunks.get_punk(999), Ok(()));
+            assert!(cryptopunks.is_sold_out());
+            assert_eq!(cryptopunks.get_punk(500), Err(Error::SoldOut));
+
+            cryptopunks.assert_invariants();
+        }
+
+        // We test if the owner can reserve specific punks
+        #[ink::test]
+        fn reserve_indices_works() {
+            let mut cryptopunks = Cryptopunks::new();
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+
+            assert_eq!(cryptopunks.reserve_indices(vec![10, 50, 999]), Ok(()));
+            for punk_index in [10, 50, 999] {
+                assert_eq!(
+                    cryptopunks.punk_index_to_address.get(punk_index),
+                    Some(accounts.alice)
+                );
+            }
+            assert_eq!(cryptopunks.balance_of.get(accounts.alice), Some(3));
+            assert_eq!(cryptopunks.number_of_punks_reserved(), 3);
+            assert_eq!(cryptopunks.punks_remaining_to_assign, 997);
+
+            assert_eq!(
+                cryptopunks.reserve_indices(vec![1000]),
+                Err(Error::PunkIndexOutOfRange)
+            );
+            assert_eq!(
+                cryptopunks.reserve_indices(vec![11, 50]),
+                Err(Error::PunkAlreadyAssigned)
+            );
+            assert_eq!(
+                cryptopunks.reserve_indices(vec![12, 12]),
+                Err(Error::DuplicateIndex)
+            );
+
+            set_sender(accounts.bob);
+            assert_eq!(cryptopunks.reserve_indices(vec![1]), Err(Error::NotOwner));
+
+            cryptopunks.assert_invariants();
+        }
+
+        // We test if the owner can reserve a range of punks
+        #[ink::test]
+        fn reserve_range_works() {
+            let mut cryptopunks = Cryptopunks::new();
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+
+            assert_eq!(cryptopunks.reserve_range(10, 20, false), Ok(()));
+            assert_eq!(cryptopunks.owner_of(9), None);
+            for punk_index in 10..20 {
+                assert_eq!(cryptopunks.owner_of(punk_index), Some(accounts.alice));
+            }
+            assert_eq!(cryptopunks.owner_of(20), None);
+            assert_eq!(cryptopunks.number_of_punks_reserved(), 10);
+
+            assert_eq!(
+                cryptopunks.reserve_range(5, 11, false),
+                Err(Error::PunkAlreadyAssigned)
+            );
+            assert_eq!(
+                cryptopunks.reserve_range(990, 1001, false),
+                Err(Error::PunkIndexOutOfRange)
+            );
+            assert_eq!(
+                cryptopunks.reserve_range(30, 25, false),
+                Err(Error::PunkIndexOutOfRange)
+            );
+            assert_eq!(
+                cryptopunks.reserve_range(0, u32::MAX, false),
+                Err(Error::PunkIndexOutOfRange)
+            );
+
+            cryptopunks.assert_invariants();
+        }
+
+        // We test if a silent reservation emits a single summary event
+        #[ink::test]
+        fn reserve_range_silent_works() {
+            let mut cryptopunks = Cryptopunks::new();
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+
+            assert_eq!(cryptopunks.reserve_range(0, 50, true), Ok(()));
+            assert_eq!(cryptopunks.owner_of(49), Some(accounts.alice));
+
+            let events = recorded_events();
+            assert_eq!(events.len(), 1);
+            match &events[0] {
+                Event::BatchAssigned(event) => {
+                    assert_eq!(event.to, accounts.alice);
+                    assert_eq!(event.count, 50);
+                }
+                _ => panic!("Expected a BatchAssigned event"),
+            }
+
+            cryptopunks.assert_invariants();
+        }
+
+        // We test if the latest assignments are kept for a "just minted" feed
+        #[ink::test]
+        fn recent_punks_works() {
+            let mut cryptopunks = Cryptopunks::new();
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            assert_eq!(cryptopunks.recent_punks(), vec![]);
+
+            set_sender(accounts.bob);
+            for punk_index in [7, 3, 9] {
+                assert_eq!(cryptopunks.get_punk(punk_index), Ok(()));
+            }
+            assert_eq!(cryptopunks.recent_punks(), vec![7, 3, 9]);
+
+            for punk_index in 100..120 {
+                assert_eq!(cryptopunks.get_punk(punk_index), Ok(()));
+            }
+            assert_eq!(cryptopunks.recent_punks(), (104..120).collect::<Vec<_>>());
+
+            cryptopunks.assert_invariants();
+        }
+
+        // We test if Alice can obtain an available Punk
+        #[ink::test]
+        fn get_works() {
+            let mut cryptopunks = Cryptopunks::new();
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let _balance =
+                ink::env::test::get_account_balance::<ink::env::DefaultEnvironment>(accounts.alice)
+                    .expect("Alice has no Account Balance");
+            assert_eq!(cryptopunks.get_punk(0), Ok(()));
+
+            cryptopunks.assert_invariants();
+        }
+
+        // We test if a punk beyond the total supply can't be obtained
+        #[ink::test]
+        fn get_punk_out_of_range_fails() {
+            let mut cryptopunks = Cryptopunks::new();
+            assert_eq!(cryptopunks.get_punk(1000), Err(Error::PunkIndexOutOfRange));
+            assert_eq!(cryptopunks.get_punk(5000), Err(Error::PunkIndexOutOfRange));
+            assert_eq!(cryptopunks.config().1, 1000);
+            assert_eq!(cryptopunks.get_punk(999), Ok(()));
+
+            cryptopunks.assert_invariants();
+        }
+
+        // We test if a punk reserved for the owner can't be obtained by the public
+        #[ink::test]
+        fn get_reserved_punk_fails() {
+            let mut cryptopunks = Cryptopunks::new();
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            assert_eq!(cryptopunks.reserve_punks_for_owner(1), Ok(1));
+
+            set_sender(accounts.bob);
+            assert_eq!(cryptopunks.get_punk(0), Err(Error::PunkAlreadyAssigned));
+            assert_eq!(cryptopunks.get_punk(1), Ok(()));
+
+            cryptopunks.assert_invariants();
+        }
+
+        // We test if Alice can obtain a Punk and sell it to Charlie
+        #[ink::test]
+        fn sale_works() {
+            let mut cryptopunks = Cryptopunks::new();
+
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let balance =
+                ink::env::test::get_account_balance::<ink::env::DefaultEnvironment>(accounts.alice)
+                    .expect("Alice has no Account Balance");
+
+            set_sender(accounts.alice);
+
+            assert_eq!(cryptopunks.get_punk(0), Ok(()));
+
+            assert_eq!(cryptopunks.offer_punk_for_sale(0, 100000, None), Ok(()));
+
+            set_sender(accounts.charlie);
+            set_balance(accounts.charlie, 200000);
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(100000);
+
+            assert_eq!(cryptopunks.buy_punk(0), Ok(()));
+
+            set_sender(accounts.alice);
+
+            assert_eq!(cryptopunks.withdraw(), Ok(()));
+
+            let new_balance =
+                ink::env::test::get_account_balance::<ink::env::DefaultEnvironment>(accounts.alice)
+                    .expect("Alice has no Account Balance");
+
+            assert_eq!(balance + 100000, new_balance);
+
+            cryptopunks.assert_invariants();
+        }
+
+        // We test if the contract stays solvent through a sale and its withdrawal
+        #[ink::test]
+        fn is_solvent_works() {
+            let mut cryptopunks = Cryptopunks::new();
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+
+            set_sender(accounts.alice);
+            assert!(cryptopunks.is_solvent());
+            assert_eq!(cryptopunks.get_punk(0), Ok(()));
+            assert_eq!(cryptopunks.offer_punk_for_sale(0, 100000, None), Ok(()));
+
+            set_sender(accounts.charlie);
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(100000);
+            assert_eq!(cryptopunks.buy_punk(0), Ok(()));
+            assert!(cryptopunks.is_solvent());
+
+            set_sender(accounts.alice);
+            assert_eq!(cryptopunks.withdraw(), Ok(()));
+            assert!(cryptopunks.is_solvent());
+
+            cryptopunks.assert_invariants();
+        }
+
+        // We test if only the owner can terminate the contract and only without pending funds
+        #[ink::test]
+        fn terminate_guards_work() {
+            let mut cryptopunks = Cryptopunks::new();
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+
+            set_sender(accounts.bob);
+            assert_eq!(cryptopunks.get_punk(0), Ok(()));
+            assert_eq!(cryptopunks.offer_punk_for_sale(0, 100000, None), Ok(()));
+            assert_eq!(cryptopunks.terminate(), Err(Error::NotOwner));
+
+            set_sender(accounts.charlie);
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(100000);
+            assert_eq!(cryptopunks.buy_punk(0), Ok(()));
+
+            set_sender(accounts.alice);
+            assert_eq!(cryptopunks.terminate(), Err(Error::PendingWithdrawalsExist));
+
+            cryptopunks.assert_invariants();
+        }
+
+        // We test if Alice can take all her offers off the market at once
+        #[ink::test]
+        fn cancel_all_offers_works() {
+            let mut cryptopunks = Cryptopunks::new();
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+
+            set_sender(accounts.alice);
+            for punk_index in 0..3 {
+                assert_eq!(cryptopunks.get_punk(punk_index), Ok(()));
+                assert_eq!(
+                    cryptopunks.offer_punk_for_sale(punk_index, 100000, None),
+                    Ok(())
+                );
+            }
+            assert_eq!(cryptopunks.get_punk(3), Ok(()));
+
+            set_sender(accounts.bob);
+            assert_eq!(cryptopunks.get_punk(4), Ok(()));
+            assert_eq!(cryptopunks.offer_punk_for_sale(4, 100000, None), Ok(()));
+
+            set_sender(accounts.alice);
+            cryptopunks.cancel_all_offers(vec![0, 1, 2, 3, 4, 5]);
+
+            for punk_index in 0..3 {
+                let offer = cryptopunks.punks_offered_for_sale.get(punk_index).unwrap();
+                assert!(!offer.is_for_sale);
+            }
+            assert!(
+                cryptopunks
+                    .punks_offered_for_sale
+                    .get(4)
+                    .unwrap()
+                    .is_for_sale
+            );
+
+            cryptopunks.assert_invariants();
+        }
+
+        // We test if the owner can push Bob's proceeds to him
+        #[ink::test]
+        fn force_push_withdrawal_works() {
+            let mut cryptopunks = Cryptopunks::new();
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let balance =
+                ink::env::test::get_account_balance::<ink::env::DefaultEnvironment>(accounts.bob)
+                    .expect("Bob has no Account Balance");
+
+            set_sender(accounts.bob);
+            assert_eq!(cryptopunks.get_punk(0), Ok(()));
+            assert_eq!(cryptopunks.offer_punk_for_sale(0, 100000, None), Ok(()));
+
+            set_sender(accounts.charlie);
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(100000);
+            assert_eq!(cryptopunks.buy_punk(0), Ok(()));
+            assert_eq!(
+                cryptopunks.force_push_withdrawal(accounts.bob),
+                Err(Error::NotOwner)
+            );
+
+            set_sender(accounts.alice);
+            assert_eq!(cryptopunks.force_push_withdrawal(accounts.bob), Ok(()));
+            assert_eq!(
+                cryptopunks.force_push_withdrawal(accounts.bob),
+                Err(Error::NoPendingWithdrawals)
+            );
+
+            let new_balance =
+                ink::env::test::get_account_balance::<ink::env::DefaultEnvironment>(accounts.bob)
+                    .expect("Bob has no Account Balance");
+            assert_eq!(balance + 100000, new_balance);
+
+            cryptopunks.assert_invariants();
+        }
+
+        // We test if the contract's surplus is split 60/40 between Bob and Charlie
+        #[ink::test]
+        fn distribute_works() {
+            let mut cryptopunks = Cryptopunks::new();
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let contract = ink::env::account_id::<ink::env::DefaultEnvironment>();
+
+            set_sender(accounts.alice);
+            assert_eq!(cryptopunks.get_punk(0), Ok(()));
+            assert_eq!(cryptopunks.offer_punk_for_sale(0, 100000, None), Ok(()));
+            set_sender(accounts.charlie);
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(100000);
+            assert_eq!(cryptopunks.buy_punk(0), Ok(()));
+            set_balance(contract, 150000);
+
+            assert_eq!(
+                cryptopunks.distribute(vec![(accounts.bob, 6000), (accounts.charlie, 4000)]),
+                Err(Error::NotOwner)
+            );
+            set_sender(accounts.alice);
+            assert_eq!(
+                cryptopunks.distribute(vec![(accounts.bob, 6000), (accounts.charlie, 3000)]),
+                Err(Error::InvalidShares)
+            );
+            assert_eq!(
+                cryptopunks.distribute(vec![(accounts.bob, 6000), (accounts.charlie, 4000)]),
+                Ok(())
+            );
+            assert_eq!(
+                cryptopunks.pending_withdrawals.get(accounts.alice),
+                Some(100000)
+            );
+            assert_eq!(
+                cryptopunks.pending_withdrawals.get(accounts.bob),
+                Some(30000)
+            );
+            assert_eq!(
+                cryptopunks.pending_withdrawals.get(accounts.charlie),
+                Some(20000)
+            );
+
+            cryptopunks.assert_invariants();
+        }
+
+        // We test if Alice has claimable funds only between a sale and her withdrawal
+        #[ink::test]
+        fn has_pending_works() {
+            let mut cryptopunks = Cryptopunks::new();
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+
+            set_sender(accounts.alice);
+            assert_eq!(cryptopunks.get_punk(0), Ok(()));
+            assert_eq!(cryptopunks.offer_punk_for_sale(0, 100000, None), Ok(()));
+            assert!(!cryptopunks.has_pending(accounts.alice));
+
+            set_sender(accounts.charlie);
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(100000);
+            assert_eq!(cryptopunks.buy_punk(0), Ok(()));
+            assert!(cryptopunks.has_pending(accounts.alice));
+
+            set_sender(accounts.alice);
+            assert_eq!(cryptopunks.withdraw(), Ok(()));
+            assert!(!cryptopunks.has_pending(accounts.alice));
+
+            cryptopunks.assert_invariants();
+        }
+
+        // We test if refunds and earnings are accounted apart but withdrawn together
+        #[ink::test]
+        fn refunds_and_earnings_works() {
+            let mut cryptopunks = Cryptopunks::new();
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+
+            set_sender(accounts.alice);
+            assert_eq!(cryptopunks.set_transfer_fee(1000), Ok(()));
+
+            set_sender(accounts.bob);
+            assert_eq!(cryptopunks.get_punk(0), Ok(()));
+            assert_eq!(cryptopunks.get_punk(1), Ok(()));
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(1200);
+            assert_eq!(
+                cryptopunks.transfer_punk_with_fee(accounts.charlie, 0),
+                Ok(())
+            );
+            assert_eq!(cryptopunks.offer_punk_for_sale(1, 100000, None), Ok(()));
+
+            set_sender(accounts.django);
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(100000);
+            assert_eq!(cryptopunks.buy_punk(1), Ok(()));
+
+            assert_eq!(cryptopunks.refunds_of(accounts.bob), 200);
+            assert_eq!(cryptopunks.earnings_of(accounts.bob), 100000);
+            assert_eq!(cryptopunks.earnings_of(accounts.alice), 1000);
+
+            set_sender(accounts.bob);
+            let balance =
+                ink::env::test::get_account_balance::<ink::env::DefaultEnvironment>(accounts.bob)
+                    .expect("Bob has no Account Balance");
+            assert_eq!(cryptopunks.withdraw(), Ok(()));
+            let new_balance =
+                ink::env::test::get_account_balance::<ink::env::DefaultEnvironment>(accounts.bob)
+                    .expect("Bob has no Account Balance");
+            assert_eq!(new_balance, balance + 100200);
+            assert_eq!(cryptopunks.refunds_of(accounts.bob), 0);
+            assert_eq!(cryptopunks.earnings_of(accounts.bob), 0);
+
+            cryptopunks.assert_invariants();
+        }
+
+        // We test if credited proceeds can only be withdrawn after the withdrawal delay
+        #[ink::test]
+        fn withdrawal_delay_works() {
+            let mut cryptopunks = Cryptopunks::new();
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+
+            set_sender(accounts.alice);
+            assert_eq!(cryptopunks.set_withdrawal_delay_blocks(10), Ok(()));
+            assert_eq!(cryptopunks.get_punk(0), Ok(()));
+            assert_eq!(cryptopunks.offer_punk_for_sale(0, 100000, None), Ok(()));
+
+            set_sender(accounts.charlie);
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(100000);
+            assert_eq!(cryptopunks.buy_punk(0), Ok(()));
+            let credited = ink::env::block_number::<ink::env::DefaultEnvironment>();
+
+            set_sender(accounts.alice);
+            assert_eq!(cryptopunks.withdrawable_at(accounts.alice), credited + 10);
+            assert_eq!(cryptopunks.withdraw(), Err(Error::WithdrawalLocked));
+            advance_blocks(9);
+            assert_eq!(cryptopunks.withdraw(), Err(Error::WithdrawalLocked));
+            advance_blocks(1);
+            assert_eq!(cryptopunks.withdraw(), Ok(()));
+            assert!(!cryptopunks.has_pending(accounts.alice));
+
+            cryptopunks.assert_invariants();
+        }
+
+        // We test if a zero-fee transfer doesn't extend the lock on the owner's funds
+        #[ink::test]
+        fn zero_credit_keeps_withdrawal_lock() {
+            let mut cryptopunks = Cryptopunks::new();
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+
+            set_sender(accounts.alice);
+            assert_eq!(cryptopunks.set_withdrawal_delay_blocks(10), Ok(()));
+            assert_eq!(cryptopunks.get_punk(0), Ok(()));
+            assert_eq!(cryptopunks.offer_punk_for_sale(0, 100000, None), Ok(()));
+
+            set_sender(accounts.charlie);
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(100000);
+            assert_eq!(cryptopunks.buy_punk(0), Ok(()));
+            let unlocked_at = cryptopunks.withdrawable_at(accounts.alice);
+
+            advance_blocks(9);
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(0);
+            assert_eq!(cryptopunks.transfer_punk_with_fee(accounts.bob, 0), Ok(()));
+            assert_eq!(cryptopunks.withdrawable_at(accounts.alice), unlocked_at);
+
+            advance_blocks(1);
+            set_sender(accounts.alice);
+            assert_eq!(cryptopunks.withdraw(), Ok(()));
+
+            cryptopunks.assert_invariants();
+        }
+
+        // We test if credits arriving within the delay don't hold back earlier ones
+        #[ink::test]
+        fn withdrawal_delay_unlocks_each_credit_on_time() {
+            let mut cryptopunks = Cryptopunks::new();
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+
+            set_sender(accounts.alice);
+            assert_eq!(cryptopunks.set_withdrawal_delay_blocks(10), Ok(()));
+            for punk_index in 0..4 {
+                assert_eq!(cryptopunks.get_punk(punk_index), Ok(()));
+                assert_eq!(
+                    cryptopunks.offer_punk_for_sale(punk_index, 1000, None),
+                    Ok(())
+                );
+            }
+
+            // Alice's sales are credited every 9 blocks, each within the delay of
+            // the one before.
+            set_sender(accounts.charlie);
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(1000);
+            assert_eq!(cryptopunks.buy_punk(0), Ok(()));
+            advance_blocks(1);
+            for punk_index in 1..4 {
+                advance_blocks(8);
+                assert_eq!(cryptopunks.buy_punk(punk_index), Ok(()));
+                assert_eq!(
+                    cryptopunks.withdrawable_amount(accounts.alice),
+                    1000 * (u128::from(punk_index) - 1)
+                );
+                advance_blocks(1);
+                assert_eq!(
+                    cryptopunks.withdrawable_amount(accounts.alice),
+                    1000 * u128::from(punk_index)
+                );
+            }
+
+            set_sender(accounts.alice);
+            assert_eq!(cryptopunks.withdraw(), Ok(()));
+            assert_eq!(cryptopunks.earnings_of(accounts.alice), 1000);
+            assert_eq!(cryptopunks.withdraw(), Err(Error::WithdrawalLocked));
+
+            cryptopunks.assert_invariants();
+        }
+
+        // We test if credits made in every block can't keep the earliest ones locked
+        #[ink::test]
+        fn withdrawal_delay_bounds_lock_under_constant_credits() {
+            let mut cryptopunks = Cryptopunks::new();
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+
+            set_sender(accounts.alice);
+            assert_eq!(cryptopunks.set_withdrawal_delay_blocks(10), Ok(()));
+            for block in 0..30 {
+                cryptopunks.credit_pending(accounts.alice, 1);
+                advance_blocks(1);
+                let withdrawable = cryptopunks.withdrawable_amount(accounts.alice);
+                match block {
+                    0..=8 => assert_eq!(withdrawable, 0),
+                    9..=17 => assert_eq!(withdrawable, 1),
+                    _ => assert!(withdrawable >= 10),
+                }
+            }
+        }
+
+        // We test if a new credit only locks itself, not proceeds that already matured
+        #[ink::test]
+        fn withdrawal_delay_keeps_matured_funds_withdrawable() {
+            let mut cryptopunks = Cryptopunks::new();
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+
+            set_sender(accounts.alice);
+            assert_eq!(
+                cryptopunks.set_withdrawal_delay_blocks(MAX_WITHDRAWAL_DELAY_BLOCKS + 1),
+                Err(Error::WithdrawalDelayTooLong)
+            );
+            assert_eq!(cryptopunks.set_withdrawal_delay_blocks(10), Ok(()));
+            assert_eq!(cryptopunks.get_punk(0), Ok(()));
+            assert_eq!(cryptopunks.get_punk(1), Ok(()));
+            assert_eq!(cryptopunks.offer_punk_for_sale(0, 100000, None), Ok(()));
+            assert_eq!(cryptopunks.offer_punk_for_sale(1, 50000, None), Ok(()));
+
+            set_sender(accounts.charlie);
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(100000);
+            assert_eq!(cryptopunks.buy_punk(0), Ok(()));
+            advance_blocks(10);
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(50000);
+            assert_eq!(cryptopunks.buy_punk(1), Ok(()));
+
+            set_sender(accounts.alice);
+            assert_eq!(cryptopunks.withdrawable_amount(accounts.alice), 100000);
+            assert_eq!(cryptopunks.withdraw(), Ok(()));
+            assert_eq!(cryptopunks.earnings_of(accounts.alice), 50000);
+            assert_eq!(cryptopunks.withdraw(), Err(Error::WithdrawalLocked));
+            advance_blocks(10);
+            assert_eq!(cryptopunks.withdrawable_at(accounts.alice), 0);
+            assert_eq!(cryptopunks.withdraw(), Ok(()));
+            assert!(!cryptopunks.has_pending(accounts.alice));
+
+            cryptopunks.assert_invariants();
+        }
+
+        // We test if Alice can withdraw half of her proceeds and claim the rest later
+        #[ink::test]
+        fn withdraw_amount_works() {
+            let mut cryptopunks = Cryptopunks::new();
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let balance =
+                ink::env::test::get_account_balance::<ink::env::DefaultEnvironment>(accounts.alice)
+                    .expect("Alice has no Account Balance");
+
+            set_sender(accounts.alice);
+            assert_eq!(cryptopunks.get_punk(0), Ok(()));
+            assert_eq!(cryptopunks.offer_punk_for_sale(0, 100000, None), Ok(()));
+
+            set_sender(accounts.charlie);
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(100000);
+            assert_eq!(cryptopunks.buy_punk(0), Ok(()));
+
+            set_sender(accounts.alice);
+            assert_eq!(cryptopunks.withdraw_amount(0), Err(Error::ZeroAmount));
+            assert_eq!(
+                cryptopunks.withdraw_amount(100001),
+                Err(Error::AmountExceedsPending)
+            );
+            assert_eq!(cryptopunks.withdraw_amount(50000), Ok(()));
+            assert_eq!(
+                cryptopunks.pending_withdrawals.get(accounts.alice),
+                Some(50000)
+            );
+            assert_eq!(cryptopunks.withdraw(), Ok(()));
+
+            let new_balance =
+                ink::env::test::get_account_balance::<ink::env::DefaultEnvironment>(accounts.alice)
+                    .expect("Alice has no Account Balance");
+            assert_eq!(balance + 100000, new_balance);
+
+            cryptopunks.assert_invariants();
+        }
+
+        // We test if a contract account can withdraw its proceeds
+        #[ink::test]
+        fn withdraw_to_contract_works() {
+            let mut cryptopunks = Cryptopunks::new();
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let marketplace = AccountId::from([0x07; 32]);
+            ink::env::test::set_contract::<ink::env::DefaultEnvironment>(marketplace);
+            assert!(ink::env::test::is_contract::<ink::env::DefaultEnvironment>(
+                marketplace
+            ));
+
+            set_sender(marketplace);
+            assert_eq!(cryptopunks.get_punk(0), Ok(()));
+            assert_eq!(cryptopunks.offer_punk_for_sale(0, 100000, None), Ok(()));
+
+            set_sender(accounts.charlie);
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(100000);
+            assert_eq!(cryptopunks.buy_punk(0), Ok(()));
+
+            set_sender(marketplace);
+            assert_eq!(cryptopunks.withdraw(), Ok(()));
+            assert_eq!(
+                ink::env::test::get_account_balance::<ink::env::DefaultEnvironment>(marketplace),
+                Ok(100000)
+            );
+            assert!(!cryptopunks.has_pending(marketplace));
+
+            cryptopunks.assert_invariants();
+        }
+
+        // We test if every payout is refused while the contract is insolvent
+        #[ink::test]
+        fn payouts_fail_while_insolvent() {
+            let contract = AccountId::from([0x42; 32]);
+            ink::env::test::set_callee::<ink::env::DefaultEnvironment>(contract);
+            set_balance(contract, 0);
+            let mut cryptopunks = Cryptopunks::new();
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+
+            set_sender(accounts.alice);
+            assert_eq!(cryptopunks.get_punk(0), Ok(()));
+            assert_eq!(cryptopunks.offer_punk_for_sale(0, 100000, None), Ok(()));
+
+            set_sender(accounts.charlie);
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(100000);
+            assert_eq!(cryptopunks.buy_punk(0), Ok(()));
+
+            set_balance(contract, 60000);
+            set_sender(accounts.alice);
+            assert!(!cryptopunks.is_solvent());
+            assert_eq!(cryptopunks.withdraw(), Err(Error::Insolvent));
+            assert_eq!(cryptopunks.withdraw_amount(50000), Err(Error::Insolvent));
+            assert_eq!(
+                cryptopunks.force_push_withdrawal(accounts.alice),
+                Err(Error::Insolvent)
+            );
+            assert_eq!(
+                cryptopunks.pending_withdrawals.get(accounts.alice),
+                Some(100000)
+            );
+
+            set_balance(contract, 100000);
+            assert_eq!(cryptopunks.withdraw_amount(50000), Ok(()));
+            assert_eq!(cryptopunks.withdraw(), Ok(()));
+            assert_eq!(cryptopunks.pending_withdrawals.get(accounts.alice), Some(0));
+
+            cryptopunks.assert_invariants();
+        }
+
+        // We test if Alice's proceeds stay claimable when paying them out fails
+        #[ink::test]
+        fn withdraw_recredits_on_transfer_failure() {
+            // Transfers fail while the contract account has no balance in the engine.
+            let contract = AccountId::from([0x42; 32]);
+            ink::env::test::set_callee::<ink::env::DefaultEnvironment>(contract);
+            let mut cryptopunks = Cryptopunks::new();
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+
+            set_sender(accounts.alice);
+            assert_eq!(cryptopunks.get_punk(0), Ok(()));
+            assert_eq!(cryptopunks.offer_punk_for_sale(0, 100000, None), Ok(()));
+
+            set_sender(accounts.charlie);
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(100000);
+            assert_eq!(cryptopunks.buy_punk(0), Ok(()));
+
+            // The engine can't report the balance of a missing contract account, so
+            // the transfer is tried without the solvency check of `pay_out`.
+            assert_eq!(
+                cryptopunks.transfer_pending(accounts.alice, 100000),
+                Err(Error::TransferFailed)
+            );
+            assert_eq!(
+                cryptopunks.pending_withdrawals.get(accounts.alice),
+                Some(100000)
+            );
+            assert_eq!(
+                cryptopunks.transfer_pending(accounts.alice, 50000),
+                Err(Error::TransferFailed)
+            );
+            assert_eq!(
+                cryptopunks.pending_withdrawals.get(accounts.alice),
+                Some(100000)
+            );
+            assert_eq!(cryptopunks.total_pending, 100000);
+
+            set_balance(contract, 100000);
+            set_sender(accounts.alice);
+            assert_eq!(cryptopunks.withdraw(), Ok(()));
+            assert_eq!(cryptopunks.pending_withdrawals.get(accounts.alice), Some(0));
+
+            cryptopunks.assert_invariants();
+        }
+
+        // We test if a sale is recorded in the buyer's and seller's totals
+        #[ink::test]
+        fn spent_and_earned_works() {
+            let mut cryptopunks = Cryptopunks::new();
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+
+            set_sender(accounts.alice);
+            assert_eq!(cryptopunks.get_punk(0), Ok(()));
+            assert_eq!(cryptopunks.offer_punk_for_sale(0, 100000, None), Ok(()));
+
+            set_sender(accounts.charlie);
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(120000);
+            assert_eq!(cryptopunks.buy_punk(0), Ok(()));
+
+            assert_eq!(cryptopunks.spent_by(accounts.charlie), 100000);
+            assert_eq!(cryptopunks.earned_by(accounts.alice), 100000);
+            assert_eq!(cryptopunks.earnings_of(accounts.alice), 100000);
+            assert_eq!(cryptopunks.refunds_of(accounts.charlie), 20000);
+            assert_eq!(cryptopunks.spent_by(accounts.alice), 0);
+            assert_eq!(cryptopunks.earned_by(accounts.charlie), 0);
+
+            cryptopunks.assert_invariants();
+        }
+
+        // We test if a purchase hands the punk over to the buyer and emits PunkTransfer
+        #[ink::test]
+        fn buy_punk_transfers_punk() {
+            let mut cryptopunks = Cryptopunks::new();
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+
+            set_sender(accounts.alice);
+            assert_eq!(cryptopunks.get_punk(0), Ok(()));
+            assert_eq!(cryptopunks.offer_punk_for_sale(0, 100000, None), Ok(()));
+
+            set_sender(accounts.charlie);
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(100000);
+            assert_eq!(cryptopunks.buy_punk(0), Ok(()));
+
+            assert_eq!(cryptopunks.owner_of(0), Some(accounts.charlie));
+            assert!(recorded_events().iter().any(|event| matches!(
+                event,
+                Event::PunkTransfer(event)
+                    if event.from == accounts.alice
+                        && event.to == accounts.charlie
+                        && event.punk_index == 0
+            )));
+
+            cryptopunks.assert_invariants();
+        }
+
+        // We test if a gifted punk can only be claimed for free by its recipient
+        #[ink::test]
+        fn gift_works() {
+            let mut cryptopunks = Cryptopunks::new();
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+
+            set_sender(accounts.alice);
+            assert_eq!(cryptopunks.get_punk(0), Ok(()));
+            assert_eq!(cryptopunks.get_punk(1), Ok(()));
+            assert_eq!(
+                cryptopunks.gift_to(0, accounts.alice),
+                Err(Error::SelfTransfer)
+            );
+            assert_eq!(
+                cryptopunks.gift_to(0, AccountId::from([0x0; 32])),
+                Err(Error::ZeroAddress)
+            );
+            assert_eq!(cryptopunks.set_transfer_fee(1000), Ok(()));
+            assert_eq!(cryptopunks.gift_to(0, accounts.bob), Ok(()));
+            assert_eq!(cryptopunks.offer_punk_for_sale(1, 0, None), Ok(()));
+
+            set_sender(accounts.charlie);
+            assert_eq!(
+                cryptopunks.claim_gift(0),
+                Err(Error::PunkReservedForOtherBuyer)
+            );
+            assert_eq!(
+                cryptopunks.claim_gift(1),
+                Err(Error::PunkReservedForOtherBuyer)
+            );
+
+            set_sender(accounts.bob);
+            assert_eq!(cryptopunks.claim_gift(0), Ok(()));
+            assert_eq!(cryptopunks.owner_of(0), Some(accounts.bob));
+            assert_eq!(cryptopunks.punk_offer(0), None);
+            assert!(!cryptopunks.has_pending(accounts.alice));
+
+            cryptopunks.assert_invariants();
+        }
+
+        // We test if Charlie can buy two listed punks in one call and gets the leftover back
+        #[ink::test]
+        fn buy_punks_works() {
+            let mut cryptopunks = Cryptopunks::new();
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+
+            set_sender(accounts.alice);
+            assert_eq!(cryptopunks.get_punk(0), Ok(()));
+            assert_eq!(cryptopunks.offer_punk_for_sale(0, 100000, None), Ok(()));
+            set_sender(accounts.bob);
+            assert_eq!(cryptopunks.get_punk(1), Ok(()));
+            assert_eq!(cryptopunks.offer_punk_for_sale(1, 50000, None), Ok(()));
+
+            set_sender(accounts.charlie);
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(140000);
+            assert_eq!(
+                cryptopunks.buy_punks(vec![0, 1]),
+                Err(Error::InsufficientPayment {
+                    expected: 150000,
+                    actual: 140000,
+                })
+            );
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(160000);
+            assert_eq!(cryptopunks.buy_punks(vec![0, 1]), Ok(()));
+
+            assert_eq!(cryptopunks.punk_offer(0), None);
+            assert_eq!(cryptopunks.punk_offer(1), None);
+            assert_eq!(cryptopunks.earned_by(accounts.alice), 100000);
+            assert_eq!(cryptopunks.earned_by(accounts.bob), 50000);
+            assert_eq!(cryptopunks.spent_by(accounts.charlie), 150000);
+            assert_eq!(cryptopunks.refunds_of(accounts.charlie), 10000);
+
+            cryptopunks.assert_invariants();
+        }
+
+        // We test if a batch whose prices overflow when summed is rejected
+        #[ink::test]
+        fn buy_punks_fails_on_price_overflow() {
+            let mut cryptopunks = Cryptopunks::new();
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+
+            set_sender(accounts.alice);
+            assert_eq!(cryptopunks.get_punk(0), Ok(()));
+            assert_eq!(cryptopunks.get_punk(1), Ok(()));
+            assert_eq!(cryptopunks.offer_punk_for_sale(0, u128::MAX, None), Ok(()));
+            assert_eq!(cryptopunks.offer_punk_for_sale(1, 10, None), Ok(()));
+
+            set_sender(accounts.bob);
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(9);
+            assert_eq!(cryptopunks.buy_punks(vec![0, 1]), Err(Error::PriceOverflow));
+            assert_eq!(cryptopunks.owner_of(0), Some(accounts.alice));
+            assert_eq!(cryptopunks.owner_of(1), Some(accounts.alice));
+            assert_eq!(cryptopunks.earnings_of(accounts.alice), 0);
+
+            cryptopunks.assert_invariants();
+        }
+
+        // We test if a second buyer of the same offer gets a clean error and pays nothing
+        #[ink::test]
+        fn buy_punk_fails_when_already_bought() {
+            let mut cryptopunks = Cryptopunks::new();
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+
+            set_sender(accounts.alice);
+            assert_eq!(cryptopunks.get_punk(0), Ok(()));
+            assert_eq!(cryptopunks.offer_punk_for_sale(0, 100000, None), Ok(()));
+
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(100000);
+            set_sender(accounts.charlie);
+            assert_eq!(cryptopunks.buy_punk(0), Ok(()));
+            set_sender(accounts.django);
+            assert_eq!(cryptopunks.buy_punk(0), Err(Error::PunkNotForSale));
+
+            assert_eq!(cryptopunks.spent_by(accounts.django), 0);
+            assert_eq!(cryptopunks.earned_by(accounts.alice), 100000);
+            assert_eq!(cryptopunks.pending_withdrawals.get(accounts.django), None);
+
+            cryptopunks.assert_invariants();
+        }
+
+        // We test if an underpaying buyer learns the required amount
+        #[ink::test]
+        fn buy_punk_fails_on_insufficient_payment() {
+            let mut cryptopunks = Cryptopunks::new();
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+
+            set_sender(accounts.alice);
+            assert_eq!(cryptopunks.get_punk(0), Ok(()));
+            assert_eq!(cryptopunks.offer_punk_for_sale(0, 100000, None), Ok(()));
+
+            set_sender(accounts.charlie);
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(0);
+
+            assert_eq!(
+                cryptopunks.buy_punk(0),
+                Err(Error::InsufficientPayment {
+                    expected: 100000,
+                    actual: 0,
+                })
+            );
+
+            cryptopunks.assert_invariants();
+        }
+
+        // We test if a freshly claimed punk can only be listed after the hold period
+        #[ink::test]
+        fn hold_period_works() {
+            let mut cryptopunks = Cryptopunks::new();
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+
+            set_sender(accounts.alice);
+            assert_eq!(cryptopunks.set_min_hold_blocks(10), Ok(()));
+            assert_eq!(cryptopunks.get_punk(0), Ok(()));
+
+            advance_blocks(9);
+            assert_eq!(
+                cryptopunks.offer_punk_for_sale(0, 100000, None),
+                Err(Error::HoldPeriodActive)
+            );
+            assert_eq!(
+                cryptopunks.transfer_punk(accounts.bob, 0),
+                Err(Error::HoldPeriodActive)
+            );
+
+            advance_blocks(1);
+            assert_eq!(cryptopunks.offer_punk_for_sale(0, 100000, None), Ok(()));
+            assert_eq!(cryptopunks.transfer_punk(accounts.bob, 0), Ok(()));
+
+            set_sender(accounts.bob);
+            assert_eq!(cryptopunks.set_min_hold_blocks(0), Err(Error::NotOwner));
+
+            cryptopunks.assert_invariants();
+        }
+
+        // We test if the active offers of a seller are counted
+        #[ink::test]
+        fn active_offers_count_works() {
+            let mut cryptopunks = Cryptopunks::new();
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+
+            set_sender(accounts.alice);
+            for punk_index in 0..4 {
+                assert_eq!(cryptopunks.get_punk(punk_index), Ok(()));
+                assert_eq!(
+                    cryptopunks.offer_punk_for_sale(punk_index, 100000, None),
+                    Ok(())
+                );
+            }
+            assert_eq!(cryptopunks.relist(0, 200000, None), Ok(()));
+            assert_eq!(cryptopunks.active_offers_count(accounts.alice), 4);
+
+            cryptopunks.cancel_all_offers(vec![0]);
+            assert_eq!(cryptopunks.transfer_punk(accounts.bob, 1), Ok(()));
+            set_sender(accounts.charlie);
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(100000);
+            assert_eq!(cryptopunks.buy_punk(2), Ok(()));
+            assert_eq!(cryptopunks.active_offers_count(accounts.alice), 1);
+            assert_eq!(cryptopunks.active_offers_count(accounts.bob), 0);
+
+            cryptopunks.assert_invariants();
+        }
+
+        // We test if all listed punks are returned with their prices
+        #[ink::test]
+        fn punks_for_sale_works() {
+            let mut cryptopunks = Cryptopunks::new();
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+
+            set_sender(accounts.alice);
+            assert_eq!(cryptopunks.get_punk(0), Ok(()));
+            assert_eq!(cryptopunks.get_punk(1), Ok(()));
+            assert_eq!(cryptopunks.get_punk(2), Ok(()));
+            assert_eq!(cryptopunks.offer_punk_for_sale(0, 100000, None), Ok(()));
+            assert_eq!(cryptopunks.offer_punk_for_sale(1, 200000, None), Ok(()));
+            assert_eq!(cryptopunks.relist(1, 300000, None), Ok(()));
+            assert_eq!(cryptopunks.punks_for_sale(), vec![(0, 100000), (1, 300000)]);
+
+            set_sender(accounts.charlie);
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(100000);
+            assert_eq!(cryptopunks.buy_punk(0), Ok(()));
+            assert_eq!(cryptopunks.punks_for_sale(), vec![(1, 300000)]);
+
+            cryptopunks.assert_invariants();
+        }
+
+        // We test if delisting a punk keeps the remaining listings indexed
+        #[ink::test]
+        fn delisting_keeps_listings_indexed() {
+            let mut cryptopunks = Cryptopunks::new();
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+
+            set_sender(accounts.alice);
+            for punk_index in 0..3 {
+                assert_eq!(cryptopunks.get_punk(punk_index), Ok(()));
+                assert_eq!(
+                    cryptopunks.offer_punk_for_sale(punk_index, 100, None),
+                    Ok(())
+                );
+            }
+            cryptopunks.cancel_all_offers(vec![0]);
+            assert_eq!(cryptopunks.punks_for_sale(), vec![(2, 100), (1, 100)]);
+            cryptopunks.cancel_all_offers(vec![1]);
+            assert_eq!(cryptopunks.punks_for_sale(), vec![(2, 100)]);
+            assert_eq!(cryptopunks.offer_punk_for_sale(0, 200, None), Ok(()));
+            assert_eq!(cryptopunks.punks_for_sale(), vec![(2, 100), (0, 200)]);
+
+            cryptopunks.assert_invariants();
+        }
+
+        // We test if the listings can be paged through
+        #[ink::test]
+        fn punks_for_sale_page_works() {
+            let mut cryptopunks = Cryptopunks::new();
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+
+            set_sender(accounts.alice);
+            for punk_index in 0..5 {
+                assert_eq!(cryptopunks.get_punk(punk_index), Ok(()));
+                assert_eq!(
+                    cryptopunks.offer_punk_for_sale(punk_index, 100, None),
+                    Ok(())
+                );
+            }
+
+            assert_eq!(
+                cryptopunks.punks_for_sale_page(0, 2),
+                (vec![(0, 100), (1, 100)], Some(2))
+            );
+            assert_eq!(
+                cryptopunks.punks_for_sale_page(2, 2),
+                (vec![(2, 100), (3, 100)], Some(4))
+            );
+            assert_eq!(
+                cryptopunks.punks_for_sale_page(4, 2),
+                (vec![(4, 100)], None)
+            );
+            assert_eq!(cryptopunks.punks_for_sale_page(6, 2), (vec![], None));
+            assert_eq!(cryptopunks.punks_for_sale_page(1, 0), (vec![], None));
+
+            cryptopunks.assert_invariants();
+        }
+
+        // We test if only the given seller's listings are returned
+        #[ink::test]
+        fn offers_by_works() {
+            let mut cryptopunks = Cryptopunks::new();
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+
+            set_sender(accounts.alice);
+            assert_eq!(cryptopunks.get_punk(0), Ok(()));
+            assert_eq!(cryptopunks.get_punk(1), Ok(()));
+            assert_eq!(cryptopunks.offer_punk_for_sale(0, 100, None), Ok(()));
+            assert_eq!(cryptopunks.offer_punk_for_sale(1, 200, None), Ok(()));
+            set_sender(accounts.bob);
+            assert_eq!(cryptopunks.get_punk(2), Ok(()));
+            assert_eq!(cryptopunks.offer_punk_for_sale(2, 300, None), Ok(()));
+
+            assert_eq!(
+                cryptopunks.offers_by(accounts.alice),
+                vec![(0, 100), (1, 200)]
+            );
+            assert_eq!(cryptopunks.offers_by(accounts.bob), vec![(2, 300)]);
+            assert_eq!(cryptopunks.offers_by(accounts.charlie), vec![]);
+
+            cryptopunks.assert_invariants();
+        }
+
+        // We test if the floor price is the cheapest listing and vanishes with the last one
+        #[ink::test]
+        fn floor_price_works() {
+            let mut cryptopunks = Cryptopunks::new();
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+
+            set_sender(accounts.alice);
+            assert_eq!(cryptopunks.floor_price(), None);
+            assert_eq!(cryptopunks.get_punk(0), Ok(()));
+            assert_eq!(cryptopunks.get_punk(1), Ok(()));
+            assert_eq!(cryptopunks.offer_punk_for_sale(0, 300, None), Ok(()));
+            assert_eq!(cryptopunks.offer_punk_for_sale(1, 100, None), Ok(()));
+            assert_eq!(cryptopunks.floor_price(), Some(100));
+
+            cryptopunks.cancel_all_offers(vec![1]);
+            assert_eq!(cryptopunks.floor_price(), Some(300));
+            cryptopunks.cancel_all_offers(vec![0]);
+            assert_eq!(cryptopunks.floor_price(), None);
+
+            cryptopunks.assert_invariants();
+        }
+
+        // We test if the balances of several accounts are returned in one call
+        #[ink::test]
+        fn balances_of_works() {
+            let mut cryptopunks = Cryptopunks::new();
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+
+            set_sender(accounts.alice);
+            assert_eq!(cryptopunks.get_punk(0), Ok(()));
+            assert_eq!(cryptopunks.get_punk(1), Ok(()));
+            set_sender(accounts.bob);
+            assert_eq!(cryptopunks.get_punk(2), Ok(()));
+
+            assert_eq!(
+                cryptopunks.balances_of(vec![accounts.alice, accounts.bob, accounts.charlie]),
+                vec![2, 1, 0]
+            );
+
+            cryptopunks.assert_invariants();
+        }
+
+        // We test if the aggregate stats follow mints, listings and sales
+        #[ink::test]
+        fn stats_works() {
+            let mut cryptopunks = Cryptopunks::new();
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            assert_eq!(cryptopunks.stats(), (0, 0, 0, 0));
+
+            set_sender(accounts.alice);
+            assert_eq!(cryptopunks.get_punk(0), Ok(()));
+            assert_eq!(cryptopunks.get_punk(1), Ok(()));
+            assert_eq!(cryptopunks.offer_punk_for_sale(0, 300, None), Ok(()));
+            assert_eq!(cryptopunks.offer_punk_for_sale(1, 100, None), Ok(()));
+            set_sender(accounts.bob);
+            assert_eq!(cryptopunks.get_punk(2), Ok(()));
+            assert_eq!(cryptopunks.stats(), (3, 2, 100, 2));
+
+            set_sender(accounts.charlie);
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(100);
+            assert_eq!(cryptopunks.buy_punk(1), Ok(()));
+            assert_eq!(cryptopunks.stats(), (3, 1, 300, 3));
+
+            cryptopunks.assert_invariants();
+        }
+
+        // We test if holders are counted once they own a punk and not after
+        #[ink::test]
+        fn holder_count_works() {
+            let mut cryptopunks = Cryptopunks::new();
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            assert_eq!(cryptopunks.holder_count(), 0);
+
+            set_sender(accounts.alice);
+            assert_eq!(cryptopunks.get_punk(0), Ok(()));
+            assert_eq!(cryptopunks.get_punk(1), Ok(()));
+            assert_eq!(cryptopunks.holder_count(), 1);
+
+            set_sender(accounts.bob);
+            assert_eq!(cryptopunks.get_punk(2), Ok(()));
+            assert_eq!(cryptopunks.holder_count(), 2);
+            assert_eq!(cryptopunks.transfer_punk(accounts.charlie, 2), Ok(()));
+            assert_eq!(cryptopunks.holder_count(), 2);
+
+            set_sender(accounts.alice);
+            assert_eq!(cryptopunks.transfer_punk(accounts.charlie, 0), Ok(()));
+            assert_eq!(cryptopunks.holder_count(), 2);
+            assert_eq!(cryptopunks.transfer_punk(accounts.charlie, 1), Ok(()));
+            assert_eq!(cryptopunks.holder_count(), 1);
+
+            cryptopunks.assert_invariants();
+        }
+
+        // We test if a transfer requires the configured fee, credited to the owner
+        #[ink::test]
+        fn transfer_fee_works() {
+            let mut cryptopunks = Cryptopunks::new();
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+
+            set_sender(accounts.alice);
+            assert_eq!(cryptopunks.set_transfer_fee(1000), Ok(()));
+            set_sender(accounts.bob);
+            assert_eq!(cryptopunks.set_transfer_fee(0), Err(Error::NotOwner));
+            assert_eq!(cryptopunks.get_punk(0), Ok(()));
+
+            assert_eq!(
+                cryptopunks.transfer_punk(accounts.charlie, 0),
+                Err(Error::InsufficientPayment {
+                    expected: 1000,
+                    actual: 0,
+                })
+            );
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(999);
+            assert_eq!(
+                cryptopunks.transfer_punk_with_fee(accounts.charlie, 0),
+                Err(Error::InsufficientPayment {
+                    expected: 1000,
+                    actual: 999,
+                })
+            );
+            assert_eq!(cryptopunks.owner_of(0), Some(accounts.bob));
+
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(1200);
+            assert_eq!(
+                cryptopunks.transfer_punk_with_fee(accounts.charlie, 0),
+                Ok(())
+            );
+            assert_eq!(cryptopunks.owner_of(0), Some(accounts.charlie));
+            assert_eq!(
+                cryptopunks.pending_withdrawals.get(accounts.alice),
+                Some(1000)
+            );
+            assert_eq!(cryptopunks.refunds_of(accounts.bob), 200);
+
+            cryptopunks.assert_invariants();
+        }
+
+        // We test if the emitted events carry increasing sequence numbers
+        #[ink::test]
+        fn event_seq_works() {
+            let mut cryptopunks = Cryptopunks::new();
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+
+            set_sender(accounts.alice);
+            assert_eq!(cryptopunks.get_punk(0), Ok(()));
+            assert_eq!(cryptopunks.offer_punk_for_sale(0, 100000, None), Ok(()));
+            assert_eq!(cryptopunks.transfer_punk(accounts.bob, 0), Ok(()));
+
+            let seqs: Vec<u64> = recorded_events()
+                .into_iter()
+                .map(|event| match event {
+                    Event::PunkNoLongerForSale(event) => event.seq,
+                    Event::PunkOffered(event) => event.seq,
+                    Event::Transfer(event) => event.seq,
+                    Event::PunkTransfer(event) => event.seq,
+                    Event::BatchAssigned(event) => event.seq,
+                    Event::ReservationComplete(event) => event.seq,
+                    Event::Paused(event) => event.seq,
+                    Event::Unpaused(event) => event.seq,
+                    Event::OwnershipProposed(event) => event.seq,
+                    Event::OwnershipTransferred(event) => event.seq,
+                    Event::Assign(event) => event.seq,
+                })
+                .collect();
+            assert_eq!(seqs, vec![1, 2, 3, 4, 5]);
+
+            cryptopunks.assert_invariants();
+        }
+
+        // We test if a transfer memo is emitted with PunkTransfer and capped in length
+        #[ink::test]
+        fn transfer_punk_with_memo_works() {
+            let mut cryptopunks = Cryptopunks::new();
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+
+            set_sender(accounts.alice);
+            assert_eq!(cryptopunks.get_punk(0), Ok(()));
+            assert_eq!(
+                cryptopunks.transfer_punk_with_memo(accounts.bob, 0, vec![0x1; 65]),
+                Err(Error::MemoTooLong)
+            );
+            assert_eq!(
+                cryptopunks.transfer_punk_with_memo(accounts.bob, 0, b"order-42".to_vec()),
+                Ok(())
+            );
+            assert_eq!(cryptopunks.owner_of(0), Some(accounts.bob));
+            assert!(matches!(
+                recorded_events().last(),
+                Some(Event::PunkTransfer(event)) if event.memo == b"order-42".to_vec()
+            ));
+
+            set_sender(accounts.alice);
+            assert_eq!(cryptopunks.set_transfer_fee(1000), Ok(()));
+            set_sender(accounts.bob);
+            assert_eq!(
+                cryptopunks.transfer_punk_with_memo(accounts.charlie, 0, b"gift".to_vec()),
+                Err(Error::InsufficientPayment {
+                    expected: 1000,
+                    actual: 0,
+                })
+            );
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(1000);
+            assert_eq!(
+                cryptopunks.transfer_punk_with_memo(accounts.charlie, 0, b"gift".to_vec()),
+                Ok(())
+            );
+            assert_eq!(cryptopunks.owner_of(0), Some(accounts.charlie));
+            assert_eq!(cryptopunks.earnings_of(accounts.alice), 1000);
+
+            cryptopunks.assert_invariants();
+        }
+
+        // We test if the original minter of a punk survives transfers
+        #[ink::test]
+        fn minter_of_works() {
+            let mut cryptopunks = Cryptopunks::new();
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+
+            set_sender(accounts.alice);
+            assert_eq!(cryptopunks.minter_of(0), None);
+            assert_eq!(cryptopunks.get_punk(0), Ok(()));
+            assert_eq!(cryptopunks.transfer_punk(accounts.bob, 0), Ok(()));
+
+            assert_eq!(cryptopunks.owner_of(0), Some(accounts.bob));
+            assert_eq!(cryptopunks.minter_of(0), Some(accounts.alice));
+
+            cryptopunks.assert_invariants();
+        }
+
+        // We test if a punk can't be transferred to the zero account
+        #[ink::test]
+        fn transfer_to_zero_address_fails() {
+            let mut cryptopunks = Cryptopunks::new();
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+
+            set_sender(accounts.alice);
+            assert_eq!(cryptopunks.get_punk(0), Ok(()));
+            assert_eq!(
+                cryptopunks.transfer_punk(AccountId::from([0x0; 32]), 0),
+                Err(Error::ZeroAddress)
+            );
+            assert_eq!(cryptopunks.owner_of(0), Some(accounts.alice));
+
+            cryptopunks.assert_invariants();
+        }
+
+        // We test if the block of the last ownership change is recorded
+        #[ink::test]
+        fn last_transfer_block_works() {
+            let mut cryptopunks = Cryptopunks::new();
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let block = ink::env::block_number::<ink::env::DefaultEnvironment>();
+
+            assert_eq!(cryptopunks.last_transfer_block_of(0), None);
+            set_sender(accounts.alice);
+            assert_eq!(cryptopunks.get_punk(0), Ok(()));
+            assert_eq!(cryptopunks.last_transfer_block_of(0), Some(block));
+
+            advance_blocks(5);
+            assert_eq!(cryptopunks.transfer_punk(accounts.bob, 0), Ok(()));
+            assert_eq!(cryptopunks.last_transfer_block_of(0), Some(block + 5));
+
+            cryptopunks.assert_invariants();
+        }
+
+        // We test if transferring a punk to its owner is rejected without touching balances
+        #[ink::test]
+        fn self_transfer_fails() {
+            let mut cryptopunks = Cryptopunks::new();
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+
+            set_sender(accounts.alice);
+            assert_eq!(cryptopunks.get_punk(0), Ok(()));
+            assert_eq!(
+                cryptopunks.transfer_punk(accounts.alice, 0),
+                Err(Error::SelfTransfer)
+            );
+            assert_eq!(cryptopunks.balance_of.get(accounts.alice), Some(1));
+            assert_eq!(
+                cryptopunks.punk_index_to_address.get(0),
+                Some(accounts.alice)
+            );
+
+            assert_eq!(cryptopunks.transfer_punk(accounts.bob, 0), Ok(()));
+            assert_eq!(cryptopunks.balance_of.get(accounts.alice), Some(0));
+            assert_eq!(cryptopunks.balance_of.get(accounts.bob), Some(1));
+
+            cryptopunks.assert_invariants();
+        }
+
+        // We test if an offer stays open while blocks pass, as offers don't expire
+        #[ink::test]
+        fn offer_does_not_expire() {
+            let mut cryptopunks = Cryptopunks::new();
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+
+            set_sender(accounts.alice);
+            assert_eq!(cryptopunks.get_punk(0), Ok(()));
+            assert_eq!(cryptopunks.offer_punk_for_sale(0, 100000, None), Ok(()));
+
+            let block = ink::env::block_number::<ink::env::DefaultEnvironment>();
+            advance_blocks(100);
+            assert_eq!(
+                ink::env::block_number::<ink::env::DefaultEnvironment>(),
+                block + 100
+            );
+
+            set_sender(accounts.charlie);
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(100000);
+            assert_eq!(cryptopunks.buy_punk(0), Ok(()));
+
+            cryptopunks.assert_invariants();
+        }
+
+        // We test if a private offer announces the designated buyer and creation time
+        #[ink::test]
+        fn punk_offered_event_works() {
+            let mut cryptopunks = Cryptopunks::new();
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+
+            set_sender(accounts.alice);
+            assert_eq!(cryptopunks.get_punk(0), Ok(()));
+            advance_blocks(1);
+            assert_eq!(
+                cryptopunks.offer_punk_for_sale(0, 100000, Some(accounts.bob)),
+                Ok(())
+            );
+
+            let offered = recorded_events()
+                .into_iter()
+                .find_map(|event| match event {
+                    Event::PunkOffered(event) => Some(event),
+                    _ => None,
+                })
+                .expect("No PunkOffered event");
+            assert_eq!(offered.punk_index, 0);
+            assert_eq!(offered.address, Some(accounts.bob));
+            assert_eq!(
+                offered.created_at,
+                ink::env::block_timestamp::<ink::env::DefaultEnvironment>()
+            );
+
+            cryptopunks.assert_invariants();
+        }
+
+        // We test if the offer getters return the punk index the offer was stored under
+        #[ink::test]
+        fn offer_punk_index_works() {
+            let mut cryptopunks = Cryptopunks::new();
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+
+            set_sender(accounts.alice);
+            assert_eq!(cryptopunks.get_punk(3), Ok(()));
+            assert_eq!(cryptopunks.get_punk(7), Ok(()));
+            assert_eq!(cryptopunks.offer_punk_for_sale(3, 100000, None), Ok(()));
+            assert_eq!(cryptopunks.offer_punk_for_sale(7, 200000, None), Ok(()));
+            assert_eq!(cryptopunks.reserve_punk(7), Ok(()));
+
+            assert_eq!(
+                cryptopunks.punk_offer(3).map(|offer| offer.punk_index),
+                Some(3)
+            );
+            assert_eq!(
+                cryptopunks.offer_history(7).map(|offer| offer.punk_index),
+                Some(7)
+            );
+
+            cryptopunks.assert_invariants();
+        }
+
+        // We test if a sold punk's offer is only visible in the offer history
+        #[ink::test]
+        fn offer_history_works() {
+            let mut cryptopunks = Cryptopunks::new();
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+
+            set_sender(accounts.alice);
+            assert_eq!(cryptopunks.get_punk(0), Ok(()));
+            assert_eq!(cryptopunks.punk_offer(0), None);
+            assert_eq!(cryptopunks.offer_history(0), None);
+
+            assert_eq!(cryptopunks.offer_punk_for_sale(0, 100000, None), Ok(()));
+            let offer = cryptopunks.punk_offer(0).expect("Punk is offered");
+            assert_eq!(offer.min_value, 100000);
+            assert_eq!(cryptopunks.offer_history(0), Some(offer));
+
+            set_sender(accounts.charlie);
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(100000);
+            assert_eq!(cryptopunks.buy_punk(0), Ok(()));
+            assert_eq!(cryptopunks.punk_offer(0), None);
+            let disabled = cryptopunks.offer_history(0).expect("Offer is recorded");
+            assert!(!disabled.is_for_sale);
+            assert_eq!(disabled.punk_index, 0);
+            assert_eq!(disabled.seller, accounts.alice);
+            assert_eq!(disabled.min_value, 100000);
+
+            assert_eq!(
+                cryptopunks.offer_punk_for_sale(0, 200000, Some(accounts.bob)),
+                Ok(())
+            );
+            set_sender(accounts.alice);
+            assert_eq!(cryptopunks.force_cancel_offer(0), Ok(()));
+            let cancelled = cryptopunks.offer_history(0).expect("Offer is recorded");
+            assert!(!cancelled.is_for_sale);
+            assert_eq!(cancelled.seller, accounts.charlie);
+            assert_eq!(cancelled.min_value, 200000);
+            assert_eq!(cancelled.only_sell_to, Some(accounts.bob));
+
+            cryptopunks.assert_invariants();
+        }
+
+        // We test if the designated buyer of an active offer is returned
+        #[ink::test]
+        fn offer_reserved_for_works() {
+            let mut cryptopunks = Cryptopunks::new();
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+
+            set_sender(accounts.alice);
+            assert_eq!(cryptopunks.get_punk(0), Ok(()));
+            assert_eq!(cryptopunks.get_punk(1), Ok(()));
+            assert_eq!(
+                cryptopunks.offer_punk_for_sale(0, 100000, Some(accounts.bob)),
+                Ok(())
+            );
+            assert_eq!(cryptopunks.offer_punk_for_sale(1, 100000, None), Ok(()));
+
+            assert_eq!(cryptopunks.offer_reserved_for(0), Some(accounts.bob));
+            assert_eq!(cryptopunks.offer_reserved_for(1), None);
+            cryptopunks.cancel_all_offers(vec![0]);
+            assert_eq!(cryptopunks.offer_reserved_for(0), None);
+
+            cryptopunks.assert_invariants();
+        }
+
+        // We test if the seller of an active offer is returned
+        #[ink::test]
+        fn offer_seller_works() {
+            let mut cryptopunks = Cryptopunks::new();
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+
+            set_sender(accounts.bob);
+            assert_eq!(cryptopunks.get_punk(0), Ok(()));
+            assert_eq!(cryptopunks.offer_seller(0), None);
+            assert_eq!(cryptopunks.offer_punk_for_sale(0, 100000, None), Ok(()));
+            assert_eq!(cryptopunks.offer_seller(0), cryptopunks.owner_of(0));
+            assert_eq!(cryptopunks.offer_seller(0), Some(accounts.bob));
+
+            cryptopunks.cancel_all_offers(vec![0]);
+            assert_eq!(cryptopunks.offer_seller(0), None);
+
+            cryptopunks.assert_invariants();
+        }
+
+        // We test if an offer is stale after the seller transferred the punk away
+        #[ink::test]
+        fn is_offer_valid_works() {
+            let mut cryptopunks = Cryptopunks::new();
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+
+            set_sender(accounts.alice);
+            assert_eq!(cryptopunks.get_punk(0), Ok(()));
+            assert!(!cryptopunks.is_offer_valid(0));
+            assert_eq!(cryptopunks.offer_punk_for_sale(0, 100000, None), Ok(()));
+            assert!(cryptopunks.is_offer_valid(0));
+
+            assert_eq!(cryptopunks.transfer_punk(accounts.bob, 0), Ok(()));
+            assert!(!cryptopunks.is_offer_valid(0));
+
+            cryptopunks.assert_invariants();
+        }
+
+        // We test if the owner can take Bob's listing off the market
+        #[ink::test]
+        fn force_cancel_offer_works() {
+            let mut cryptopunks = Cryptopunks::new();
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+
+            set_sender(accounts.bob);
+            assert_eq!(cryptopunks.get_punk(0), Ok(()));
+            assert_eq!(cryptopunks.offer_punk_for_sale(0, 100000, None), Ok(()));
+            assert_eq!(cryptopunks.force_cancel_offer(0), Err(Error::NotOwner));
+
+            set_sender(accounts.alice);
+            assert_eq!(cryptopunks.force_cancel_offer(0), Ok(()));
+            assert_eq!(cryptopunks.punk_offer(0), None);
+            assert_eq!(cryptopunks.active_offers_count(accounts.bob), 0);
+            assert!(matches!(
+                recorded_events().last(),
+                Some(Event::PunkNoLongerForSale(event)) if event.punk_index == 0
+            ));
+            assert_eq!(
+                cryptopunks.force_cancel_offer(0),
+                Err(Error::PunkNotForSale)
+            );
+
+            cryptopunks.assert_invariants();
+        }
+
+        // We test if a punk kept off the market is distinguished from a listed one
+        #[ink::test]
+        fn reserve_punk_works() {
+            let mut cryptopunks = Cryptopunks::new();
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+
+            set_sender(accounts.alice);
+            assert_eq!(cryptopunks.get_punk(0), Ok(()));
+            assert_eq!(cryptopunks.get_punk(1), Ok(()));
+            assert_eq!(cryptopunks.offer_punk_for_sale(0, 100000, None), Ok(()));
+            assert_eq!(cryptopunks.reserve_punk(1), Ok(()));
+            assert!(!cryptopunks.is_held_off_market(0));
+            assert!(cryptopunks.is_held_off_market(1));
+            assert_eq!(cryptopunks.punks_for_sale(), vec![(0, 100000)]);
+
+            assert_eq!(cryptopunks.reserve_punk(0), Ok(()));
+            assert!(cryptopunks.is_held_off_market(0));
+            assert_eq!(cryptopunks.punks_for_sale(), vec![]);
+
+            assert_eq!(cryptopunks.offer_punk_for_sale(1, 200000, None), Ok(()));
+            assert!(!cryptopunks.is_held_off_market(1));
+
+            set_sender(accounts.bob);
+            assert_eq!(cryptopunks.reserve_punk(1), Err(Error::NotPunkOwner));
+
+            cryptopunks.assert_invariants();
+        }
+
+        // We test if pausing and resuming are reflected by is_paused and signalled once
+        #[ink::test]
+        fn is_paused_works() {
+            let mut cryptopunks = Cryptopunks::new();
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+
+            set_sender(accounts.alice);
+            assert!(!cryptopunks.is_paused());
+            assert_eq!(cryptopunks.set_buying_paused(true), Ok(()));
+            assert!(cryptopunks.is_paused());
+            assert!(matches!(
+                recorded_events().last(),
+                Some(Event::Paused(event)) if event.by == accounts.alice
+            ));
+            let events = recorded_events().len();
+            assert_eq!(cryptopunks.set_buying_paused(true), Ok(()));
+            assert_eq!(recorded_events().len(), events);
+
+            assert_eq!(cryptopunks.set_buying_paused(false), Ok(()));
+            assert!(!cryptopunks.is_paused());
+            assert!(matches!(
+                recorded_events().last(),
+                Some(Event::Unpaused(event)) if event.by == accounts.alice
+            ));
+
+            cryptopunks.assert_invariants();
+        }
+
+        // We test if sellers can still list and delist while buying is paused
+        #[ink::test]
+        fn buying_paused_works() {
+            let mut cryptopunks = Cryptopunks::new();
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+
+            set_sender(accounts.bob);
+            assert_eq!(cryptopunks.set_buying_paused(true), Err(Error::NotOwner));
+
+            set_sender(accounts.alice);
+            assert_eq!(cryptopunks.set_buying_paused(true), Ok(()));
+            assert!(cryptopunks.buying_paused());
+            assert_eq!(cryptopunks.get_punk(0), Ok(()));
+            assert_eq!(cryptopunks.get_punk(1), Ok(()));
+            assert_eq!(cryptopunks.offer_punk_for_sale(0, 100000, None), Ok(()));
+            assert_eq!(cryptopunks.offer_punk_for_sale(1, 100000, None), Ok(()));
+            cryptopunks.cancel_all_offers(vec![1]);
+
+            set_sender(accounts.charlie);
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(100000);
+            assert_eq!(cryptopunks.buy_punk(0), Err(Error::BuyingPaused));
+
+            set_sender(accounts.alice);
+            assert_eq!(cryptopunks.set_buying_paused(false), Ok(()));
+            set_sender(accounts.charlie);
+            assert_eq!(cryptopunks.buy_punk(0), Ok(()));
+
+            cryptopunks.assert_invariants();
+        }
+
+        // We test if a purchase can be checked in advance
+        #[ink::test]
+        fn can_buy_works() {
+            let mut cryptopunks = Cryptopunks::new();
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+
+            set_sender(accounts.alice);
+            assert_eq!(cryptopunks.get_punk(0), Ok(()));
+            assert_eq!(
+                cryptopunks.can_buy(0, accounts.charlie, 100000),
+                Err(Error::PunkNotForSale)
+            );
+            assert_eq!(cryptopunks.offer_punk_for_sale(0, 100000, None), Ok(()));
+
+            assert_eq!(cryptopunks.can_buy(0, accounts.charlie, 100000), Ok(()));
+            assert_eq!(
+                cryptopunks.can_buy(0, accounts.charlie, 99999),
+                Err(Error::InsufficientPayment {
+                    expected: 100000,
+                    actual: 99999,
+                })
+            );
+            assert!(cryptopunks.punk_offer(0).is_some());
+
+            cryptopunks.assert_invariants();
+        }
+
+        // We test if a reserved offer may only be bought by the designated buyer
+        #[ink::test]
+        fn can_address_buy_works() {
+            let mut cryptopunks = Cryptopunks::new();
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+
+            set_sender(accounts.alice);
+            assert_eq!(cryptopunks.get_punk(0), Ok(()));
+            assert_eq!(cryptopunks.get_punk(1), Ok(()));
+            assert!(!cryptopunks.can_address_buy(0, accounts.bob));
+            assert_eq!(
+                cryptopunks.offer_punk_for_sale(0, 100000, Some(accounts.bob)),
+                Ok(())
+            );
+            assert_eq!(cryptopunks.offer_punk_for_sale(1, 100000, None), Ok(()));
+
+            assert!(cryptopunks.can_address_buy(0, accounts.bob));
+            assert!(!cryptopunks.can_address_buy(0, accounts.charlie));
+            assert!(cryptopunks.can_address_buy(1, accounts.charlie));
+
+            cryptopunks.assert_invariants();
+        }
+
+        // We test if relisting closes the old listing before announcing the new one
+        #[ink::test]
+        fn relist_works() {
+            let mut cryptopunks = Cryptopunks::new();
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+
+            set_sender(accounts.alice);
+            assert_eq!(cryptopunks.get_punk(0), Ok(()));
+            assert_eq!(
+                cryptopunks.relist(0, 200000, None),
+                Err(Error::PunkNotForSale)
+            );
+            assert_eq!(cryptopunks.offer_punk_for_sale(0, 100000, None), Ok(()));
+            assert_eq!(
+                cryptopunks.offer_punk_for_sale(0, 200000, None),
+                Err(Error::PunkAlreadyListed)
+            );
+
+            let events_before = recorded_events().len();
+            assert_eq!(cryptopunks.relist(0, 200000, None), Ok(()));
+            let events = recorded_events();
+            assert_eq!(events.len(), events_before + 2);
+            assert!(matches!(
+                &events[events_before],
+                Event::PunkNoLongerForSale(event) if event.punk_index == 0
+            ));
+            assert!(matches!(
+                &events[events_before + 1],
+                Event::PunkOffered(event) if event.punk_index == 0 && event.min_sale_price == 200000
+            ));
+            assert_eq!(cryptopunks.punks_for_sale(), vec![(0, 200000)]);
+            assert_eq!(cryptopunks.active_offers_count(accounts.alice), 1);
+
+            cryptopunks.assert_invariants();
+        }
+
+        // We test if a guarded buy fails after the seller raised the price
+        #[ink::test]
+        fn buy_punk_at_fails_on_price_change() {
+            let mut cryptopunks = Cryptopunks::new();
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+
+            set_sender(accounts.alice);
+            assert_eq!(cryptopunks.get_punk(0), Ok(()));
+            assert_eq!(cryptopunks.offer_punk_for_sale(0, 100000, None), Ok(()));
+            assert_eq!(cryptopunks.relist(0, 150000, None), Ok(()));
+
+            set_sender(accounts.charlie);
+            set_balance(accounts.charlie, 200000);
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(150000);
+
+            assert_eq!(cryptopunks.buy_punk_at(0, 100000), Err(Error::PriceChanged));
+            assert_eq!(cryptopunks.buy_punk_at(0, 150000), Ok(()));
+            assert_eq!(
+                cryptopunks.buy_punk_at(0, 100000),
+                Err(Error::PunkNotForSale)
+            );
+            assert_eq!(
+                cryptopunks.buy_punk_at(0, 150000),
+                Err(Error::PunkNotForSale)
+            );
 
-            assert_eq!(balance, new_balance);
+            cryptopunks.assert_invariants();
         }
     }
 }